@@ -21,6 +21,7 @@ Register-ArgumentCompleter -Native -CommandName 'underdose' -ScriptBlock {
 
     $completions = @(switch ($command) {
         'underdose' {
+            [CompletionResult]::new('--lenient', '--lenient', [CompletionResultType]::ParameterName, 'only warn about tags in drips and commands that are not defined in env')
             [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('-V', '-V ', [CompletionResultType]::ParameterName, 'Print version')
@@ -28,27 +29,101 @@ Register-ArgumentCompleter -Native -CommandName 'underdose' -ScriptBlock {
             [CompletionResult]::new('init', 'init', [CompletionResultType]::ParameterValue, 'Initialize on a new machine, working from drugstore repo')
             [CompletionResult]::new('conf', 'conf', [CompletionResultType]::ParameterValue, 'Configure the machine')
             [CompletionResult]::new('where', 'where', [CompletionResultType]::ParameterValue, 'Shows all path information available')
+            [CompletionResult]::new('env', 'env', [CompletionResultType]::ParameterValue, 'Shows the detected facts and the resulting env of the machine')
             [CompletionResult]::new('sync', 'sync', [CompletionResultType]::ParameterValue, 'Make a dream on the machine, and pour if possible')
+            [CompletionResult]::new('unsync', 'unsync', [CompletionResultType]::ParameterValue, 'Dump sites into dreams without pouring')
+            [CompletionResult]::new('check', 'check', [CompletionResultType]::ParameterValue, 'Check the drugstore as every env and every known machine would load it')
+            [CompletionResult]::new('explain', 'explain', [CompletionResultType]::ParameterValue, 'Show how the drips of a pill resolve on the machine')
+            [CompletionResult]::new('status', 'status', [CompletionResultType]::ParameterValue, 'Report the state of sites on the machine')
+            [CompletionResult]::new('plan', 'plan', [CompletionResultType]::ParameterValue, 'Show what a sync would run and pour, in order, without doing it')
+            [CompletionResult]::new('list', 'list', [CompletionResultType]::ParameterValue, 'List the commands and pills on the machine')
             [CompletionResult]::new('clean', 'clean', [CompletionResultType]::ParameterValue, 'Clean up backups')
             [CompletionResult]::new('help', 'help', [CompletionResultType]::ParameterValue, 'Print this message or the help of the given subcommand(s)')
             break
         }
         'underdose;init' {
+            [CompletionResult]::new('--lenient', '--lenient', [CompletionResultType]::ParameterName, 'only warn about tags in drips and commands that are not defined in env')
             [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
             break
         }
         'underdose;conf' {
+            [CompletionResult]::new('--lenient', '--lenient', [CompletionResultType]::ParameterName, 'only warn about tags in drips and commands that are not defined in env')
             [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
             break
         }
         'underdose;where' {
+            [CompletionResult]::new('--lenient', '--lenient', [CompletionResultType]::ParameterName, 'only warn about tags in drips and commands that are not defined in env')
+            [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
+            break
+        }
+        'underdose;env' {
+            [CompletionResult]::new('--lenient', '--lenient', [CompletionResultType]::ParameterName, 'only warn about tags in drips and commands that are not defined in env')
             [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
             break
         }
         'underdose;sync' {
+            [CompletionResult]::new('-s', '-s', [CompletionResultType]::ParameterName, 'leave these out, even if required')
+            [CompletionResult]::new('--skip', '--skip', [CompletionResultType]::ParameterName, 'leave these out, even if required')
+            [CompletionResult]::new('-f', '-f', [CompletionResultType]::ParameterName, 'dump sites even if they hold local work')
+            [CompletionResult]::new('--force', '--force', [CompletionResultType]::ParameterName, 'dump sites even if they hold local work')
+            [CompletionResult]::new('--trust', '--trust', [CompletionResultType]::ParameterName, 'run new or changed commands and hooks without asking')
+            [CompletionResult]::new('--lenient', '--lenient', [CompletionResultType]::ParameterName, 'only warn about tags in drips and commands that are not defined in env')
+            [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help (see more with ''--help'')')
+            [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help (see more with ''--help'')')
+            break
+        }
+        'underdose;unsync' {
+            [CompletionResult]::new('-s', '-s', [CompletionResultType]::ParameterName, 'leave these out, even if required')
+            [CompletionResult]::new('--skip', '--skip', [CompletionResultType]::ParameterName, 'leave these out, even if required')
+            [CompletionResult]::new('-f', '-f', [CompletionResultType]::ParameterName, 'dump sites even if they hold local work')
+            [CompletionResult]::new('--force', '--force', [CompletionResultType]::ParameterName, 'dump sites even if they hold local work')
+            [CompletionResult]::new('--lenient', '--lenient', [CompletionResultType]::ParameterName, 'only warn about tags in drips and commands that are not defined in env')
+            [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
+            break
+        }
+        'underdose;check' {
+            [CompletionResult]::new('--lenient', '--lenient', [CompletionResultType]::ParameterName, 'only warn about tags in drips and commands that are not defined in env')
+            [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
+            break
+        }
+        'underdose;explain' {
+            [CompletionResult]::new('--as-env', '--as-env', [CompletionResultType]::ParameterName, 'as a machine with these tags')
+            [CompletionResult]::new('--as-machine', '--as-machine', [CompletionResultType]::ParameterName, 'as a machine declared in `[machine]` of the drugstore')
+            [CompletionResult]::new('--lenient', '--lenient', [CompletionResultType]::ParameterName, 'only warn about tags in drips and commands that are not defined in env')
+            [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
+            break
+        }
+        'underdose;status' {
+            [CompletionResult]::new('-s', '-s', [CompletionResultType]::ParameterName, 'leave these out, even if required')
+            [CompletionResult]::new('--skip', '--skip', [CompletionResultType]::ParameterName, 'leave these out, even if required')
+            [CompletionResult]::new('--as-env', '--as-env', [CompletionResultType]::ParameterName, 'as a machine with these tags')
+            [CompletionResult]::new('--as-machine', '--as-machine', [CompletionResultType]::ParameterName, 'as a machine declared in `[machine]` of the drugstore')
+            [CompletionResult]::new('--lenient', '--lenient', [CompletionResultType]::ParameterName, 'only warn about tags in drips and commands that are not defined in env')
+            [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
+            break
+        }
+        'underdose;plan' {
+            [CompletionResult]::new('-s', '-s', [CompletionResultType]::ParameterName, 'leave these out, even if required')
+            [CompletionResult]::new('--skip', '--skip', [CompletionResultType]::ParameterName, 'leave these out, even if required')
+            [CompletionResult]::new('--as-env', '--as-env', [CompletionResultType]::ParameterName, 'as a machine with these tags')
+            [CompletionResult]::new('--as-machine', '--as-machine', [CompletionResultType]::ParameterName, 'as a machine declared in `[machine]` of the drugstore')
+            [CompletionResult]::new('--lenient', '--lenient', [CompletionResultType]::ParameterName, 'only warn about tags in drips and commands that are not defined in env')
+            [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
+            [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
+            break
+        }
+        'underdose;list' {
+            [CompletionResult]::new('--as-env', '--as-env', [CompletionResultType]::ParameterName, 'as a machine with these tags')
+            [CompletionResult]::new('--as-machine', '--as-machine', [CompletionResultType]::ParameterName, 'as a machine declared in `[machine]` of the drugstore')
+            [CompletionResult]::new('--lenient', '--lenient', [CompletionResultType]::ParameterName, 'only warn about tags in drips and commands that are not defined in env')
             [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
             break
@@ -58,6 +133,7 @@ Register-ArgumentCompleter -Native -CommandName 'underdose' -ScriptBlock {
             [CompletionResult]::new('--name', '--name', [CompletionResultType]::ParameterName, 'name of the backup')
             [CompletionResult]::new('-v', '-v', [CompletionResultType]::ParameterName, 'version of the backup, can be a uuid or "all"')
             [CompletionResult]::new('--version', '--version', [CompletionResultType]::ParameterName, 'version of the backup, can be a uuid or "all"')
+            [CompletionResult]::new('--lenient', '--lenient', [CompletionResultType]::ParameterName, 'only warn about tags in drips and commands that are not defined in env')
             [CompletionResult]::new('-h', '-h', [CompletionResultType]::ParameterName, 'Print help')
             [CompletionResult]::new('--help', '--help', [CompletionResultType]::ParameterName, 'Print help')
             break
@@ -66,7 +142,14 @@ Register-ArgumentCompleter -Native -CommandName 'underdose' -ScriptBlock {
             [CompletionResult]::new('init', 'init', [CompletionResultType]::ParameterValue, 'Initialize on a new machine, working from drugstore repo')
             [CompletionResult]::new('conf', 'conf', [CompletionResultType]::ParameterValue, 'Configure the machine')
             [CompletionResult]::new('where', 'where', [CompletionResultType]::ParameterValue, 'Shows all path information available')
+            [CompletionResult]::new('env', 'env', [CompletionResultType]::ParameterValue, 'Shows the detected facts and the resulting env of the machine')
             [CompletionResult]::new('sync', 'sync', [CompletionResultType]::ParameterValue, 'Make a dream on the machine, and pour if possible')
+            [CompletionResult]::new('unsync', 'unsync', [CompletionResultType]::ParameterValue, 'Dump sites into dreams without pouring')
+            [CompletionResult]::new('check', 'check', [CompletionResultType]::ParameterValue, 'Check the drugstore as every env and every known machine would load it')
+            [CompletionResult]::new('explain', 'explain', [CompletionResultType]::ParameterValue, 'Show how the drips of a pill resolve on the machine')
+            [CompletionResult]::new('status', 'status', [CompletionResultType]::ParameterValue, 'Report the state of sites on the machine')
+            [CompletionResult]::new('plan', 'plan', [CompletionResultType]::ParameterValue, 'Show what a sync would run and pour, in order, without doing it')
+            [CompletionResult]::new('list', 'list', [CompletionResultType]::ParameterValue, 'List the commands and pills on the machine')
             [CompletionResult]::new('clean', 'clean', [CompletionResultType]::ParameterValue, 'Clean up backups')
             [CompletionResult]::new('help', 'help', [CompletionResultType]::ParameterValue, 'Print this message or the help of the given subcommand(s)')
             break
@@ -80,9 +163,30 @@ Register-ArgumentCompleter -Native -CommandName 'underdose' -ScriptBlock {
         'underdose;help;where' {
             break
         }
+        'underdose;help;env' {
+            break
+        }
         'underdose;help;sync' {
             break
         }
+        'underdose;help;unsync' {
+            break
+        }
+        'underdose;help;check' {
+            break
+        }
+        'underdose;help;explain' {
+            break
+        }
+        'underdose;help;status' {
+            break
+        }
+        'underdose;help;plan' {
+            break
+        }
+        'underdose;help;list' {
+            break
+        }
         'underdose;help;clean' {
             break
         }
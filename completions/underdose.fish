@@ -1,6 +1,6 @@
 # Print an optspec for argparse to handle cmd's options that are independent of any subcommand.
 function __fish_underdose_global_optspecs
-	string join \n h/help V/version
+	string join \n lenient h/help V/version
 end
 
 function __fish_underdose_needs_command
@@ -24,24 +24,73 @@ function __fish_underdose_using_subcommand
 	contains -- $cmd[1] $argv
 end
 
+complete -c underdose -n "__fish_underdose_needs_command" -l lenient -d 'only warn about tags in drips and commands that are not defined in env'
 complete -c underdose -n "__fish_underdose_needs_command" -s h -l help -d 'Print help'
 complete -c underdose -n "__fish_underdose_needs_command" -s V -l version -d 'Print version'
 complete -c underdose -n "__fish_underdose_needs_command" -f -a "init" -d 'Initialize on a new machine, working from drugstore repo'
 complete -c underdose -n "__fish_underdose_needs_command" -f -a "conf" -d 'Configure the machine'
 complete -c underdose -n "__fish_underdose_needs_command" -f -a "where" -d 'Shows all path information available'
+complete -c underdose -n "__fish_underdose_needs_command" -f -a "env" -d 'Shows the detected facts and the resulting env of the machine'
 complete -c underdose -n "__fish_underdose_needs_command" -f -a "sync" -d 'Make a dream on the machine, and pour if possible'
+complete -c underdose -n "__fish_underdose_needs_command" -f -a "unsync" -d 'Dump sites into dreams without pouring'
+complete -c underdose -n "__fish_underdose_needs_command" -f -a "check" -d 'Check the drugstore as every env and every known machine would load it'
+complete -c underdose -n "__fish_underdose_needs_command" -f -a "explain" -d 'Show how the drips of a pill resolve on the machine'
+complete -c underdose -n "__fish_underdose_needs_command" -f -a "status" -d 'Report the state of sites on the machine'
+complete -c underdose -n "__fish_underdose_needs_command" -f -a "plan" -d 'Show what a sync would run and pour, in order, without doing it'
+complete -c underdose -n "__fish_underdose_needs_command" -f -a "list" -d 'List the commands and pills on the machine'
 complete -c underdose -n "__fish_underdose_needs_command" -f -a "clean" -d 'Clean up backups'
 complete -c underdose -n "__fish_underdose_needs_command" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
+complete -c underdose -n "__fish_underdose_using_subcommand init" -l lenient -d 'only warn about tags in drips and commands that are not defined in env'
 complete -c underdose -n "__fish_underdose_using_subcommand init" -s h -l help -d 'Print help'
+complete -c underdose -n "__fish_underdose_using_subcommand conf" -l lenient -d 'only warn about tags in drips and commands that are not defined in env'
 complete -c underdose -n "__fish_underdose_using_subcommand conf" -s h -l help -d 'Print help'
+complete -c underdose -n "__fish_underdose_using_subcommand where" -l lenient -d 'only warn about tags in drips and commands that are not defined in env'
 complete -c underdose -n "__fish_underdose_using_subcommand where" -s h -l help -d 'Print help'
-complete -c underdose -n "__fish_underdose_using_subcommand sync" -s h -l help -d 'Print help'
+complete -c underdose -n "__fish_underdose_using_subcommand env" -l lenient -d 'only warn about tags in drips and commands that are not defined in env'
+complete -c underdose -n "__fish_underdose_using_subcommand env" -s h -l help -d 'Print help'
+complete -c underdose -n "__fish_underdose_using_subcommand sync" -s s -l skip -d 'leave these out, even if required' -r
+complete -c underdose -n "__fish_underdose_using_subcommand sync" -s f -l force -d 'dump sites even if they hold local work'
+complete -c underdose -n "__fish_underdose_using_subcommand sync" -l trust -d 'run new or changed commands and hooks without asking'
+complete -c underdose -n "__fish_underdose_using_subcommand sync" -l lenient -d 'only warn about tags in drips and commands that are not defined in env'
+complete -c underdose -n "__fish_underdose_using_subcommand sync" -s h -l help -d 'Print help (see more with \'--help\')'
+complete -c underdose -n "__fish_underdose_using_subcommand unsync" -s s -l skip -d 'leave these out, even if required' -r
+complete -c underdose -n "__fish_underdose_using_subcommand unsync" -s f -l force -d 'dump sites even if they hold local work'
+complete -c underdose -n "__fish_underdose_using_subcommand unsync" -l lenient -d 'only warn about tags in drips and commands that are not defined in env'
+complete -c underdose -n "__fish_underdose_using_subcommand unsync" -s h -l help -d 'Print help'
+complete -c underdose -n "__fish_underdose_using_subcommand check" -l lenient -d 'only warn about tags in drips and commands that are not defined in env'
+complete -c underdose -n "__fish_underdose_using_subcommand check" -s h -l help -d 'Print help'
+complete -c underdose -n "__fish_underdose_using_subcommand explain" -l as-env -d 'as a machine with these tags' -r
+complete -c underdose -n "__fish_underdose_using_subcommand explain" -l as-machine -d 'as a machine declared in `[machine]` of the drugstore' -r
+complete -c underdose -n "__fish_underdose_using_subcommand explain" -l lenient -d 'only warn about tags in drips and commands that are not defined in env'
+complete -c underdose -n "__fish_underdose_using_subcommand explain" -s h -l help -d 'Print help'
+complete -c underdose -n "__fish_underdose_using_subcommand status" -s s -l skip -d 'leave these out, even if required' -r
+complete -c underdose -n "__fish_underdose_using_subcommand status" -l as-env -d 'as a machine with these tags' -r
+complete -c underdose -n "__fish_underdose_using_subcommand status" -l as-machine -d 'as a machine declared in `[machine]` of the drugstore' -r
+complete -c underdose -n "__fish_underdose_using_subcommand status" -l lenient -d 'only warn about tags in drips and commands that are not defined in env'
+complete -c underdose -n "__fish_underdose_using_subcommand status" -s h -l help -d 'Print help'
+complete -c underdose -n "__fish_underdose_using_subcommand plan" -s s -l skip -d 'leave these out, even if required' -r
+complete -c underdose -n "__fish_underdose_using_subcommand plan" -l as-env -d 'as a machine with these tags' -r
+complete -c underdose -n "__fish_underdose_using_subcommand plan" -l as-machine -d 'as a machine declared in `[machine]` of the drugstore' -r
+complete -c underdose -n "__fish_underdose_using_subcommand plan" -l lenient -d 'only warn about tags in drips and commands that are not defined in env'
+complete -c underdose -n "__fish_underdose_using_subcommand plan" -s h -l help -d 'Print help'
+complete -c underdose -n "__fish_underdose_using_subcommand list" -l as-env -d 'as a machine with these tags' -r
+complete -c underdose -n "__fish_underdose_using_subcommand list" -l as-machine -d 'as a machine declared in `[machine]` of the drugstore' -r
+complete -c underdose -n "__fish_underdose_using_subcommand list" -l lenient -d 'only warn about tags in drips and commands that are not defined in env'
+complete -c underdose -n "__fish_underdose_using_subcommand list" -s h -l help -d 'Print help'
 complete -c underdose -n "__fish_underdose_using_subcommand clean" -s n -l name -d 'name of the backup' -r
 complete -c underdose -n "__fish_underdose_using_subcommand clean" -s v -l version -d 'version of the backup, can be a uuid or "all"' -r
+complete -c underdose -n "__fish_underdose_using_subcommand clean" -l lenient -d 'only warn about tags in drips and commands that are not defined in env'
 complete -c underdose -n "__fish_underdose_using_subcommand clean" -s h -l help -d 'Print help'
-complete -c underdose -n "__fish_underdose_using_subcommand help; and not __fish_seen_subcommand_from init conf where sync clean help" -f -a "init" -d 'Initialize on a new machine, working from drugstore repo'
-complete -c underdose -n "__fish_underdose_using_subcommand help; and not __fish_seen_subcommand_from init conf where sync clean help" -f -a "conf" -d 'Configure the machine'
-complete -c underdose -n "__fish_underdose_using_subcommand help; and not __fish_seen_subcommand_from init conf where sync clean help" -f -a "where" -d 'Shows all path information available'
-complete -c underdose -n "__fish_underdose_using_subcommand help; and not __fish_seen_subcommand_from init conf where sync clean help" -f -a "sync" -d 'Make a dream on the machine, and pour if possible'
-complete -c underdose -n "__fish_underdose_using_subcommand help; and not __fish_seen_subcommand_from init conf where sync clean help" -f -a "clean" -d 'Clean up backups'
-complete -c underdose -n "__fish_underdose_using_subcommand help; and not __fish_seen_subcommand_from init conf where sync clean help" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
+complete -c underdose -n "__fish_underdose_using_subcommand help; and not __fish_seen_subcommand_from init conf where env sync unsync check explain status plan list clean help" -f -a "init" -d 'Initialize on a new machine, working from drugstore repo'
+complete -c underdose -n "__fish_underdose_using_subcommand help; and not __fish_seen_subcommand_from init conf where env sync unsync check explain status plan list clean help" -f -a "conf" -d 'Configure the machine'
+complete -c underdose -n "__fish_underdose_using_subcommand help; and not __fish_seen_subcommand_from init conf where env sync unsync check explain status plan list clean help" -f -a "where" -d 'Shows all path information available'
+complete -c underdose -n "__fish_underdose_using_subcommand help; and not __fish_seen_subcommand_from init conf where env sync unsync check explain status plan list clean help" -f -a "env" -d 'Shows the detected facts and the resulting env of the machine'
+complete -c underdose -n "__fish_underdose_using_subcommand help; and not __fish_seen_subcommand_from init conf where env sync unsync check explain status plan list clean help" -f -a "sync" -d 'Make a dream on the machine, and pour if possible'
+complete -c underdose -n "__fish_underdose_using_subcommand help; and not __fish_seen_subcommand_from init conf where env sync unsync check explain status plan list clean help" -f -a "unsync" -d 'Dump sites into dreams without pouring'
+complete -c underdose -n "__fish_underdose_using_subcommand help; and not __fish_seen_subcommand_from init conf where env sync unsync check explain status plan list clean help" -f -a "check" -d 'Check the drugstore as every env and every known machine would load it'
+complete -c underdose -n "__fish_underdose_using_subcommand help; and not __fish_seen_subcommand_from init conf where env sync unsync check explain status plan list clean help" -f -a "explain" -d 'Show how the drips of a pill resolve on the machine'
+complete -c underdose -n "__fish_underdose_using_subcommand help; and not __fish_seen_subcommand_from init conf where env sync unsync check explain status plan list clean help" -f -a "status" -d 'Report the state of sites on the machine'
+complete -c underdose -n "__fish_underdose_using_subcommand help; and not __fish_seen_subcommand_from init conf where env sync unsync check explain status plan list clean help" -f -a "plan" -d 'Show what a sync would run and pour, in order, without doing it'
+complete -c underdose -n "__fish_underdose_using_subcommand help; and not __fish_seen_subcommand_from init conf where env sync unsync check explain status plan list clean help" -f -a "list" -d 'List the commands and pills on the machine'
+complete -c underdose -n "__fish_underdose_using_subcommand help; and not __fish_seen_subcommand_from init conf where env sync unsync check explain status plan list clean help" -f -a "clean" -d 'Clean up backups'
+complete -c underdose -n "__fish_underdose_using_subcommand help; and not __fish_seen_subcommand_from init conf where env sync unsync check explain status plan list clean help" -f -a "help" -d 'Print this message or the help of the given subcommand(s)'
@@ -0,0 +1,212 @@
+use crate::{ArrowSrc, Drugstore, Machine, env::EnvMap, utils::conf::TomlStr};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, path::Path};
+
+/// a machine sharing the drugstore, declared so that it is checked
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct KnownMachine {
+    pub env: HashSet<String>,
+}
+
+impl KnownMachine {
+    /// machines declared in `[machine]` of the drugstore
+    pub fn all(table: &toml::Table) -> anyhow::Result<IndexMap<String, Self>> {
+        match table.get("machine") {
+            | Some(known) => Ok(known.clone().try_into()?),
+            | None => Ok(IndexMap::new()),
+        }
+    }
+}
+
+/// problems of a drugstore, each with the envs it shows up on
+#[derive(Debug, Default)]
+pub struct Report {
+    pub problems: IndexMap<String, Vec<String>>,
+}
+
+impl Report {
+    fn add(&mut self, on: &str, problem: String) {
+        let envs = self.problems.entry(problem).or_default();
+        if !envs.iter().any(|env| env == on) {
+            envs.push(on.to_owned());
+        }
+    }
+
+    /// load the drugstore in `repo` as every leaf of the env tree and every
+    /// known machine would
+    pub fn check(repo: &Path, buf: &str) -> anyhow::Result<Self> {
+        let table: toml::Table = toml::from_str(buf)?;
+        let env_map = EnvMap::new(
+            table
+                .get("env")
+                .unwrap_or(&toml::Value::Table(Default::default())),
+        )?;
+        let mut machines: Vec<(String, HashSet<String>)> = (env_map.leaves().into_iter())
+            .map(|leaf| (format!("env {}", leaf), HashSet::from([leaf])))
+            .collect();
+        machines.extend(
+            (KnownMachine::all(&table)?.into_iter())
+                .map(|(name, known)| (format!("machine {}", name), known.env)),
+        );
+
+        let mut report = Report::default();
+        // pill directories used on any machine
+        let mut used = HashSet::new();
+        // conditions can't be checked off the machine, so both ways are tried
+        let machines = (machines.into_iter())
+            .flat_map(|(on, env)| [true, false].map(|assume| (on.clone(), env.clone(), assume)));
+        for (on, env, assume) in machines {
+            let mut machine = Machine {
+                local: repo.to_owned(),
+                ..Default::default()
+            }
+            .synthetic(on.clone(), env);
+            machine.assume = Some(assume);
+            let store =
+                match Drugstore::load(TomlStr::new(buf), &machine, false, &Default::default()) {
+                    | Ok(store) => store,
+                    | Err(e) => {
+                        report.add(&on, format!("failed to load: {}", e));
+                        continue;
+                    }
+                };
+            used.extend(
+                (store.files.iter())
+                    .filter_map(|file| file.components().next())
+                    .map(|c| c.as_os_str().to_owned()),
+            );
+            for overlap in store.overlaps.iter() {
+                report.add(&on, format!("sites overlap: {}", overlap));
+            }
+            for (node, names) in store.unmet.iter() {
+                report.add(
+                    &on,
+                    format!(
+                        "{} requires <{}>, which is not there",
+                        node,
+                        names.join(">, <")
+                    ),
+                );
+            }
+            for (pill, e) in store.ignored.iter() {
+                report.add(&on, format!("pill <{}> is dropped: {}", pill, e));
+            }
+            for (pill, drip) in store.pills.iter() {
+                used.extend(
+                    drip.rel_repo
+                        .components()
+                        .next()
+                        .map(|c| c.as_os_str().to_owned()),
+                );
+                for arrow in drip.arrows.iter() {
+                    let root = repo.join(&drip.rel_repo);
+                    let sources = match &arrow.src {
+                        | ArrowSrc::Link(rel) | ArrowSrc::Archive(rel) | ArrowSrc::Merge(rel) => {
+                            vec![rel]
+                        }
+                        | ArrowSrc::Block(block) => vec![&block.file],
+                        | ArrowSrc::Concat(concat) => concat.parts.iter().collect(),
+                        | ArrowSrc::Git(_) | ArrowSrc::Collector | ArrowSrc::Cmd(_) => vec![],
+                    };
+                    for rel in sources {
+                        let source = drip.rel_repo.join(rel);
+                        if !root.join(rel).exists() {
+                            report.add(
+                                &on,
+                                format!(
+                                    "pill <{}>: {} is missing from the repo",
+                                    pill,
+                                    source.display()
+                                ),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut dirs = Vec::new();
+        for entry in std::fs::read_dir(repo)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            if entry.file_type()?.is_dir() && !name.to_string_lossy().starts_with('.') {
+                dirs.push(name);
+            }
+        }
+        dirs.sort();
+        for dir in dirs {
+            if !used.contains(&dir) {
+                report.add(
+                    "any env",
+                    format!(
+                        "directory {} in repo is not used by any pill",
+                        dir.to_string_lossy()
+                    ),
+                );
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_store() {
+        let repo = std::env::temp_dir().join(format!("underdose-check-{}", std::process::id()));
+        std::fs::create_dir_all(repo.join("vim")).unwrap();
+        std::fs::create_dir_all(repo.join("stray")).unwrap();
+        std::fs::write(repo.join("vim/vimrc"), "").unwrap();
+        let buf = r#"
+            [env.sys.linux]
+            [env.sys.mac]
+            [machine.laptop]
+            env = ["mac"]
+            [[pill]]
+            name = "vim"
+            [[pill.drip]]
+            site = "~"
+            [[pill.drip.arrow]]
+            site = ".vimrc"
+            src.link = "vimrc"
+            [[pill.drip.arrow]]
+            site = ".gvimrc"
+            src.link = "gvimrc"
+            if.command = "underdose-no-such-program"
+            [[pill.drip]]
+            env = ["linux"]
+            [[pill.drip.arrow]]
+            site = ".vim"
+            src.link = "vim.d"
+            [[pill]]
+            name = "nvim"
+            [[pill.drip]]
+            env = ["mac"]
+            site = "~"
+            repo = "vim"
+            [[pill.drip.arrow]]
+            site = ".vimrc"
+            src.link = "vimrc"
+        "#;
+        let report = Report::check(&repo, buf).unwrap();
+        std::fs::remove_dir_all(&repo).unwrap();
+        let problems: Vec<_> = (report.problems.iter())
+            .map(|(problem, envs)| format!("{} on {}", problem, envs.join(", ")))
+            .collect();
+        assert_eq!(
+            problems,
+            [
+                "pill <vim>: vim/gvimrc is missing from the repo on env sys.linux, env sys.mac, \
+                 machine laptop",
+                "pill <vim>: vim/vim.d is missing from the repo on env sys.linux",
+                "sites overlap: pills <vim> and <nvim> both pour ~/.vimrc on env sys.mac, \
+                 machine laptop",
+                "directory stray in repo is not used by any pill on any env",
+            ]
+        );
+    }
+}
@@ -1,18 +1,96 @@
-use super::interface::{Cli, Commands};
+use super::interface::{AsMachine, Cli, Commands};
 use crate::{
-    Dreamer, Drugstore, Executor, Machine,
+    ArrowSrc, Dreamer, Drugstore, Executor, Ledger, Machine, Report, Stamps,
+    check::KnownMachine,
+    drugstore::{Command, Hooks, Probing},
+    observor::GitState,
+    schedule::Node,
     utils::{
-        conf::{Conf, TomlStr, UnderdoseConf},
+        conf::{Conf, Prompt, TomlStr, UnderdoseConf},
         global::UNDERDOSE_PATH,
     },
 };
 use clap::Parser;
 
+impl Default for Cli {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Cli {
     pub fn new() -> Self {
         Self::parse()
     }
+    /// read the machine configuration and the drugstore it points to, possibly
+    /// as another machine
+    fn load(
+        lenient: bool, as_machine: &AsMachine, probing: &Probing,
+    ) -> anyhow::Result<(Machine, Drugstore)> {
+        let content = Conf {
+            buffer: String::new(),
+            path: UNDERDOSE_PATH.conf.clone(),
+        }
+        .read()?;
+        let mut machine = Machine::try_from(&content[..])?;
+        let content = Conf {
+            buffer: String::new(),
+            path: machine.local.join("Drugstore.toml"),
+        }
+        .read()?;
+        if let Some(name) = &as_machine.as_machine {
+            let mut known = KnownMachine::all(&toml::from_str(&content)?)?;
+            let known = (known.swap_remove(name))
+                .ok_or_else(|| anyhow::anyhow!("no such machine in the drugstore: {}", name))?;
+            machine = machine.synthetic(name.clone(), known.env);
+        } else if !as_machine.as_env.is_empty() {
+            let env = as_machine.as_env.iter().cloned().collect();
+            machine = machine.synthetic(as_machine.as_env.join(","), env);
+        }
+        let toml = TomlStr::new(&content[..]);
+        let store = Drugstore::load(toml, &machine, lenient, probing)?;
+        for probe in store.untrusted_probes() {
+            log::warn!(
+                "probe <{}> is not trusted, so its tag is taken not to hold; `ud sync` asks to trust it",
+                probe.name
+            );
+        }
+        Ok((machine, store))
+    }
+    /// say so if sites of another machine are left with its directories
+    fn note_unresolved(machine: &Machine, store: &Drugstore) {
+        let unresolved = |path: &std::path::Path| path.to_string_lossy().contains("${");
+        let any = store.pills.values().any(|drip| {
+            unresolved(&drip.site)
+                || unresolved(&drip.rel_repo)
+                || drip.arrows.iter().any(|arrow| unresolved(&arrow.rel_site))
+        });
+        if machine.synthetic && any {
+            println!(
+                "${{config}}, ${{data}} and ${{cache}} are left for {} to resolve",
+                machine.name
+            );
+        }
+    }
+    /// ask before running commands not trusted yet, unless `trust`
+    fn approve(what: &str, untrusted: &[&Command], trust: bool) -> anyhow::Result<()> {
+        println!("new or changed {} in the drugstore:", what);
+        for cmd in untrusted.iter() {
+            println!("  <{}> {} {}", cmd.name, cmd.prog, cmd.args.join(" "));
+        }
+        if !trust {
+            Prompt::new("trust and run them? [y/N] ").process(|res| {
+                if res == "y" {
+                    Ok(())
+                } else {
+                    anyhow::bail!("{} not trusted, aborting...", what)
+                }
+            })?;
+        }
+        Ok(())
+    }
     pub fn main(self) -> anyhow::Result<()> {
+        let lenient = self.lenient;
         match self.command {
             | Commands::Init { name } => {
                 // setup underdose configuration
@@ -59,61 +137,456 @@ impl Cli {
                 print!("[dreams] ");
                 println!("{}", dreams_path);
             }
-            | Commands::Sync { names } => {
-                let content = Conf {
-                    buffer: String::new(),
-                    path: UNDERDOSE_PATH.conf.clone(),
+            | Commands::Env => {
+                let ledger = Ledger::new();
+                let probing = Probing {
+                    ledger: Some(&ledger),
+                    ..Default::default()
+                };
+                let (machine, store) = Self::load(lenient, &AsMachine::default(), &probing)?;
+                store.env.probe_all();
+                for (fact, value) in machine.facts.map.iter() {
+                    println!("[fact] {} = {}", fact, value);
                 }
-                .read()?;
-                let machine = Machine::try_from(&content[..])?;
-                let content = Conf {
-                    buffer: String::new(),
-                    path: machine.local.join("Drugstore.toml"),
+                let mut given: Vec<_> = machine.env.iter().collect();
+                given.sort();
+                for tag in given {
+                    println!("[tag] {}", tag);
+                }
+                for (tag, fact) in store.detected.iter() {
+                    println!("[tag] {} (from {})", tag, fact);
+                }
+                for (tag, probe, holds) in store.env.probed() {
+                    let verdict = match holds {
+                        | Some(true) => "yes",
+                        | Some(false) => "no",
+                        | None => "not trusted",
+                    };
+                    println!("[probe] {} if {}: {}", tag, probe, verdict);
+                }
+                println!("[env] {}", store.env.tags().join(", "));
+            }
+            | Commands::Sync {
+                names,
+                skip,
+                force,
+                trust,
+            } => {
+                let mut ledger = Ledger::new();
+                let load = |ledger: &Ledger| {
+                    let probing = Probing {
+                        names: &names,
+                        skip: &skip,
+                        ledger: Some(ledger),
+                    };
+                    Self::load(lenient, &AsMachine::default(), &probing)
+                };
+                let (mut machine, mut store) = load(&ledger)?;
+                // probes go first, as what they find may bring in more commands
+                let untrusted = store.untrusted_probes();
+                if !untrusted.is_empty() {
+                    Self::approve("probes", &untrusted, trust)?;
+                    for cmd in untrusted {
+                        ledger.trust(cmd);
+                    }
+                    ledger.write_index()?;
+                    (machine, store) = load(&ledger)?;
                 }
-                .read()?;
-                let toml = TomlStr::new(&content[..]);
-                let store = Drugstore::try_from((toml, &machine))?;
 
                 log::trace!("{:#?}", machine);
                 log::trace!("{:#?}", store);
-
-                for name in names.iter() {
-                    if !store.pills.contains_key(name) && !store.cmds.contains_key(name) {
-                        anyhow::bail!("no such pill or command: {}", name);
+                let nodes = store.select(&names, &skip)?;
+                // overlaps among pills not synced are left to `ud check`
+                let overlaps: Vec<_> = (store.overlaps.iter())
+                    .filter(|overlap| overlap.involves(&nodes))
+                    .map(ToString::to_string)
+                    .collect();
+                if !overlaps.is_empty() {
+                    anyhow::bail!("sites overlap:\n  {}", overlaps.join("\n  "));
+                }
+                let no_hooks = Hooks::default();
+                // nothing runs before every command in reach is trusted
+                let untrusted: Vec<_> = nodes
+                    .iter()
+                    .flat_map(|node| match node {
+                        | Node::Cmd(name) => vec![&store.cmds[name]],
+                        | Node::Pill(name) => {
+                            let hooks = store.hooks.get(name).unwrap_or(&no_hooks);
+                            let generators = store.generators.get(name).into_iter().flatten();
+                            (hooks.pre.iter())
+                                .chain(generators)
+                                .chain(hooks.post.iter())
+                                .collect()
+                        }
+                    })
+                    .filter(|cmd| !ledger.trusts(cmd))
+                    .collect();
+                if !untrusted.is_empty() {
+                    Self::approve("commands", &untrusted, trust)?;
+                    for cmd in untrusted {
+                        ledger.trust(cmd);
                     }
+                    ledger.write_index()?;
                 }
 
-                for (name, cmd) in store.cmds.iter() {
-                    if names.len() > 0 && !names.contains(&name) {
+                let mut dreamer = Dreamer::new();
+                let mut stamps = Stamps::new();
+                let mut failures = Vec::new();
+                for node in nodes {
+                    let name = match node {
+                        | Node::Cmd(name) => {
+                            let cmd = &store.cmds[&name];
+                            if let Some(reason) = cmd.skipped(&ledger, &store.env)? {
+                                println!("<{}> skipped: {}", name, reason);
+                                continue;
+                            }
+                            if let Err(e) = cmd.run(&store.env) {
+                                failures.push((name, e.to_string()));
+                                break;
+                            }
+                            if cmd.guard.once {
+                                ledger.once.insert(name);
+                                ledger.write_index()?;
+                            }
+                            continue;
+                        }
+                        | Node::Pill(name) => name,
+                    };
+                    // leave sites already in place alone
+                    let drip = &stamps.stale(&name, &machine.local, &store.pills[&name])?;
+                    if drip.arrows.is_empty() {
+                        log::info!("unchanged pill <{}>", name);
                         continue;
                     }
-                    log::info!(
-                        "running command <{}> :: {} {}",
-                        name,
-                        cmd.prog,
-                        cmd.args.join(" ")
-                    );
-                    let status = std::process::Command::new(&cmd.prog)
-                        .args(&cmd.args)
-                        .status()?;
-                    if !status.success() {
-                        anyhow::bail!("command failed: {}", name);
+                    let hooks = store.hooks.get(&name).unwrap_or(&no_hooks);
+                    if let Err(e) = hooks.pre.iter().try_for_each(|hook| hook.run(&store.env)) {
+                        failures.push((name, format!("pre hook failed: {}", e)));
+                        continue;
+                    }
+                    // dump current site to dreamer, then execute drip
+                    let poured = dreamer.dump(name.clone(), drip, force).and_then(|()| {
+                        Executor {
+                            name: &name,
+                            repo: &machine.local,
+                            drip,
+                            stamps: &mut stamps,
+                        }
+                        .run()
+                    });
+                    stamps.write_index()?;
+                    if let Err(e) = poured {
+                        // a half-poured machine needs a manual fix before going on
+                        failures.push((name, format!("pour failed: {}", e)));
+                        break;
+                    }
+                    if let Err(e) = hooks.post.iter().try_for_each(|hook| hook.run(&store.env)) {
+                        failures.push((name, format!("post hook failed: {}", e)));
                     }
                 }
+                for (name, e) in failures.iter() {
+                    println!("<{}> {}", name, e);
+                }
+                if !failures.is_empty() {
+                    anyhow::bail!("{} step(s) failed", failures.len());
+                }
+            }
+            | Commands::Unsync { names, skip, force } => {
+                let ledger = Ledger::new();
+                let probing = Probing {
+                    names: &names,
+                    skip: &skip,
+                    ledger: Some(&ledger),
+                };
+                let (_machine, store) = Self::load(lenient, &AsMachine::default(), &probing)?;
 
                 let mut dreamer = Dreamer::new();
-                for (name, drip) in store.pills.iter() {
-                    if names.len() > 0 && !names.contains(name) {
+                for name in store.pick_pills(&names, &skip)? {
+                    dreamer.unsync(name.to_owned(), &store.pills[name], force)?;
+                }
+            }
+            | Commands::Check { repo } => {
+                let repo = match repo {
+                    | Some(repo) => repo,
+                    | None => {
+                        let content = Conf {
+                            buffer: String::new(),
+                            path: UNDERDOSE_PATH.conf.clone(),
+                        }
+                        .read()?;
+                        Machine::try_from(&content[..])?.local
+                    }
+                };
+                let content = Conf {
+                    buffer: String::new(),
+                    path: repo.join("Drugstore.toml"),
+                }
+                .read()?;
+                let report = Report::check(&repo, &content)?;
+                for (problem, envs) in report.problems.iter() {
+                    println!("{} (on {})", problem, envs.join(", "));
+                }
+                if !report.problems.is_empty() {
+                    anyhow::bail!("{} problem(s) found", report.problems.len());
+                }
+                println!("no problems found");
+            }
+            | Commands::Explain { name, as_machine } => {
+                let ledger = Ledger::new();
+                let probing = Probing {
+                    names: std::slice::from_ref(&name),
+                    ledger: Some(&ledger),
+                    ..Default::default()
+                };
+                let (machine, store) = Self::load(lenient, &as_machine, &probing)?;
+                Self::note_unresolved(&machine, &store);
+                let trace = (store.traces.get(&name))
+                    .ok_or_else(|| anyhow::anyhow!("no such pill: {}", name))?;
+                println!("<{}> on env {}", name, store.env.tags().join(", "));
+                for (i, trace) in trace.drips.iter().enumerate() {
+                    let tags: Vec<_> = trace.tags.iter().map(ToString::to_string).collect();
+                    let tags = if tags.is_empty() {
+                        "any env".to_owned()
+                    } else {
+                        tags.join(", ")
+                    };
+                    match &trace.skipped {
+                        | None => println!("drip #{} on {}: applied", i + 1, tags),
+                        | Some(reason) => {
+                            println!("drip #{} on {}: skipped, {}", i + 1, tags, reason)
+                        }
+                    }
+                    if let Some(site) = &trace.site {
+                        println!("    site = {}", site.display());
+                    }
+                    if let Some(repo) = &trace.repo {
+                        println!("    repo = {}", repo.display());
+                    }
+                    for arrow in trace.arrows.iter() {
+                        println!("    {}: {}", arrow.rel_site.display(), arrow.src);
+                    }
+                }
+                for skipped in store
+                    .skipped
+                    .iter()
+                    .filter(|s| s.pill == name && s.arrow.is_some())
+                {
+                    println!("{}", skipped);
+                }
+                for assumed in store.assumed.iter().filter(|a| a.pill == name) {
+                    println!("{}, unknown here", assumed);
+                }
+                if let Some(e) = store.ignored.get(&name) {
+                    println!("dropped: {}", e);
+                    return Ok(());
+                }
+                if trace.disabled {
+                    println!("override in Underdose.toml: disabled");
+                    return Ok(());
+                }
+                for change in store.overridden.get(&name).into_iter().flatten() {
+                    println!("override in Underdose.toml: {}", change);
+                }
+                let Some(drip) = store.pills.get(&name) else {
+                    if machine.overrides.contains_key(&name) {
+                        println!("no drip applies, so neither does the override in Underdose.toml");
+                    } else {
+                        println!("no drip applies");
+                    }
+                    return Ok(());
+                };
+                let root = machine.local.join(&drip.rel_repo);
+                println!("resolved:");
+                println!("    site = {}", drip.site.display());
+                println!("    repo = {}", root.display());
+                for arrow in drip.arrows.iter() {
+                    let site = crate::utils::path::expand_home(drip.site.join(&arrow.rel_site));
+                    let sources = match &arrow.src {
+                        | ArrowSrc::Link(rel) | ArrowSrc::Archive(rel) | ArrowSrc::Merge(rel) => {
+                            vec![root.join(rel).display().to_string()]
+                        }
+                        | ArrowSrc::Block(block) => {
+                            vec![root.join(&block.file).display().to_string()]
+                        }
+                        | ArrowSrc::Concat(concat) => (concat.parts.iter())
+                            .map(|part| root.join(part).display().to_string())
+                            .collect(),
+                        | ArrowSrc::Git(remote) => vec![remote.clone()],
+                        | ArrowSrc::Cmd(_) | ArrowSrc::Collector => vec![],
+                    };
+                    if sources.is_empty() {
+                        println!("    {}: {}", site.display(), arrow.src);
+                    } else {
+                        println!(
+                            "    {} <- {}: {}",
+                            site.display(),
+                            sources.join(", "),
+                            arrow.src
+                        );
+                    }
+                }
+            }
+            | Commands::Plan {
+                names,
+                skip,
+                as_machine,
+            } => {
+                let ledger = Ledger::new();
+                let probing = Probing {
+                    names: &names,
+                    skip: &skip,
+                    ledger: Some(&ledger),
+                };
+                let (machine, store) = Self::load(lenient, &as_machine, &probing)?;
+                Self::note_unresolved(&machine, &store);
+                let nodes = store.select(&names, &skip)?;
+                for overlap in store.overlaps.iter() {
+                    if overlap.involves(&nodes) {
+                        println!("sync refuses, sites overlap: {}", overlap);
+                    } else {
+                        println!("sites overlap elsewhere: {}", overlap);
+                    }
+                }
+                // what is done already is only known on this machine
+                let stamps = Stamps::new();
+                let no_hooks = Hooks::default();
+                let show = |cmd: &crate::drugstore::Command| {
+                    format!("<{}> {} {}", cmd.name, cmd.prog, cmd.args.join(" "))
+                };
+                for node in nodes {
+                    match node {
+                        | Node::Cmd(name) => {
+                            let cmd = &store.cmds[&name];
+                            if !machine.synthetic
+                                && let Some(reason) = cmd.skipped_as(&ledger, None)?
+                            {
+                                println!("skip {}, {}", show(cmd), reason);
+                                continue;
+                            }
+                            let guard = &cmd.guard;
+                            let mut guards = Vec::new();
+                            if let Some(creates) = &guard.creates {
+                                guards.push(format!("unless `{}` exists", creates.display()));
+                            }
+                            if let Some(unless) = &guard.unless {
+                                guards.push(format!("unless `{}` succeeds", unless.join(" ")));
+                            }
+                            if guard.once {
+                                guards.push("once".to_owned());
+                            }
+                            if guards.is_empty() {
+                                println!("run {}", show(cmd));
+                            } else {
+                                println!("run {} ({})", show(cmd), guards.join(", "));
+                            }
+                        }
+                        | Node::Pill(name) => {
+                            let mut drip = &store.pills[&name];
+                            let stale;
+                            if !machine.synthetic {
+                                stale = stamps.stale(&name, &machine.local, drip)?;
+                                if stale.arrows.is_empty() {
+                                    println!("unchanged <{}>", name);
+                                    continue;
+                                }
+                                drip = &stale;
+                            }
+                            let hooks = store.hooks.get(&name).unwrap_or(&no_hooks);
+                            println!("pour <{}>", name);
+                            for assumed in store.assumed.iter().filter(|a| a.pill == name) {
+                                println!("    {}, unknown here", assumed);
+                            }
+                            for hook in hooks.pre.iter() {
+                                println!("    pre hook {}", show(hook));
+                            }
+                            for arrow in drip.arrows.iter() {
+                                let site = drip.site.join(&arrow.rel_site);
+                                println!("    {}: {}", site.display(), arrow.src);
+                            }
+                            for hook in hooks.post.iter() {
+                                println!("    post hook {}", show(hook));
+                            }
+                        }
+                    }
+                }
+            }
+            | Commands::List { as_machine } => {
+                let ledger = Ledger::new();
+                let probing = Probing {
+                    ledger: Some(&ledger),
+                    ..Default::default()
+                };
+                let (machine, store) = Self::load(lenient, &as_machine, &probing)?;
+                Self::note_unresolved(&machine, &store);
+                for node in store.schedule.order.iter() {
+                    match node {
+                        | Node::Cmd(name) => println!("[cmd] {}", name),
+                        | Node::Pill(name) => {
+                            println!("[pill] {} {}", name, store.pills[name].site.display());
+                            for assumed in store.assumed.iter().filter(|a| &a.pill == name) {
+                                println!("    {}, unknown here", assumed);
+                            }
+                        }
+                    }
+                }
+                for (group, members) in store.groups.iter() {
+                    println!("[group] @{} {}", group, members.join(", "));
+                }
+            }
+            | Commands::Status {
+                names,
+                skip,
+                as_machine,
+            } => {
+                let ledger = Ledger::new();
+                let probing = Probing {
+                    names: &names,
+                    skip: &skip,
+                    ledger: Some(&ledger),
+                };
+                let (machine, store) = Self::load(lenient, &as_machine, &probing)?;
+                let pills = store.pick_pills(&names, &skip)?;
+
+                for skipped in store.skipped.iter() {
+                    if (!names.is_empty() || !skip.is_empty())
+                        && !pills.contains(&skipped.pill.as_str())
+                    {
                         continue;
                     }
-                    // dump current site to dreamer
-                    dreamer.dump(name.clone(), drip)?;
-                    // execute drip
-                    Executor {
-                        repo: &machine.local,
-                        drip,
+                    println!("{}", skipped);
+                }
+                for assumed in store.assumed.iter() {
+                    if pills.contains(&assumed.pill.as_str()) {
+                        println!("{}, unknown here", assumed);
+                    }
+                }
+
+                for name in pills {
+                    let drip = &store.pills[name];
+                    for arrow in drip.arrows.iter() {
+                        let site = drip.site.join(&arrow.rel_site);
+                        let state = match &arrow.src {
+                            // sites here are not those of another machine
+                            | ArrowSrc::Git(_) | ArrowSrc::Merge(_) if machine.synthetic => {
+                                "not observed".to_owned()
+                            }
+                            | ArrowSrc::Git(_) => match GitState::observe(&site)? {
+                                | Some(state) => state.to_string(),
+                                | None => "not cloned".to_owned(),
+                            },
+                            | ArrowSrc::Merge(rel) => {
+                                let fragment = machine.local.join(&drip.rel_repo).join(rel);
+                                let site = crate::utils::path::expand_home(&site);
+                                let drift = crate::utils::merge::drift_of(&fragment, &site)?;
+                                if drift.is_empty() {
+                                    "in sync".to_owned()
+                                } else {
+                                    format!("drift on {}", drift.join(", "))
+                                }
+                            }
+                            | _ => continue,
+                        };
+                        println!("<{}> {} {}: {}", name, site.display(), arrow.src, state);
                     }
-                    .run()?;
                 }
             }
             | Commands::Clean { name, version } => {
@@ -1,10 +1,29 @@
-pub use clap::{Parser, Subcommand};
+pub use clap::{Args, Parser, Subcommand};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+    /// only warn about tags in drips and commands that are not defined in env
+    #[arg(long, global = true)]
+    pub lenient: bool,
+}
+
+/// evaluate the drugstore as another machine, touching nothing on this one
+#[derive(Args, Default)]
+pub struct AsMachine {
+    /// as a machine with these tags
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_name = "TAGS",
+        conflicts_with = "as_machine"
+    )]
+    pub as_env: Vec<String>,
+    /// as a machine declared in `[machine]` of the drugstore
+    #[arg(long, value_name = "NAME")]
+    pub as_machine: Option<String>,
 }
 
 #[derive(Subcommand)]
@@ -20,10 +39,72 @@ pub enum Commands {
     Conf,
     /// Shows all path information available
     Where,
+    /// Shows the detected facts and the resulting env of the machine
+    Env,
     /// Make a dream on the machine, and pour if possible
+    ///
+    /// Names can be pills, commands, globs over them like 'x*', or `@group`.
     Sync {
         #[arg()]
         names: Vec<String>,
+        /// leave these out, even if required
+        #[arg(short, long, value_name = "NAME")]
+        skip: Vec<String>,
+        /// dump sites even if they hold local work
+        #[arg(short, long)]
+        force: bool,
+        /// run new or changed commands and hooks without asking
+        #[arg(long)]
+        trust: bool,
+    },
+    /// Dump sites into dreams without pouring
+    Unsync {
+        #[arg()]
+        names: Vec<String>,
+        /// leave these out, even if required
+        #[arg(short, long, value_name = "NAME")]
+        skip: Vec<String>,
+        /// dump sites even if they hold local work
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Check the drugstore as every env and every known machine would load it
+    Check {
+        /// the drugstore repo, or the one of the machine if not given
+        #[arg()]
+        repo: Option<std::path::PathBuf>,
+    },
+    /// Show how the drips of a pill resolve on the machine
+    Explain {
+        #[arg(required = true)]
+        name: String,
+        #[command(flatten)]
+        as_machine: AsMachine,
+    },
+    /// Report the state of sites on the machine
+    Status {
+        #[arg()]
+        names: Vec<String>,
+        /// leave these out, even if required
+        #[arg(short, long, value_name = "NAME")]
+        skip: Vec<String>,
+        #[command(flatten)]
+        as_machine: AsMachine,
+    },
+    /// Show what a sync would run and pour, in order, without doing it
+    Plan {
+        #[arg()]
+        names: Vec<String>,
+        /// leave these out, even if required
+        #[arg(short, long, value_name = "NAME")]
+        skip: Vec<String>,
+        #[command(flatten)]
+        as_machine: AsMachine,
+    },
+    /// List the commands and pills on the machine
+    List {
+        #[command(flatten)]
+        as_machine: AsMachine,
     },
     /// Clean up backups
     Clean {
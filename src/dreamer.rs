@@ -1,4 +1,4 @@
-use crate::{Arrow, Drip, utils::global::UNDERDOSE_PATH};
+use crate::{Arrow, ArrowSrc, Drip, observor::GitState, utils::global::UNDERDOSE_PATH};
 use serde::{Deserialize, Serialize};
 use std::{
     collections::{HashMap, HashSet},
@@ -50,7 +50,7 @@ impl DreamDrip {
 
 impl Dreamer {
     pub fn path() -> &'static Path {
-        &UNDERDOSE_PATH.dreams.as_path()
+        UNDERDOSE_PATH.dreams.as_path()
     }
     fn index_path() -> PathBuf {
         Self::path().join("index.json")
@@ -69,7 +69,47 @@ impl Dreamer {
         res
     }
 
-    pub fn dump(&mut self, name: String, drip: &Drip) -> anyhow::Result<()> {
+    /// refuse to dump git clones holding local work unless forced
+    fn check_dumpable(drip: &Drip, force: bool) -> anyhow::Result<()> {
+        for Arrow { rel_site, src, .. } in drip.arrows.iter() {
+            let ArrowSrc::Git(_) = src else {
+                continue;
+            };
+            let site = drip.site.join(rel_site);
+            let Some(state) = GitState::observe(&site)? else {
+                continue;
+            };
+            if state.is_clean() {
+                continue;
+            }
+            if force {
+                log::warn!("dumping {} with local work: {}", site.display(), state);
+            } else {
+                anyhow::bail!(
+                    "refusing to dump {} with local work ({}), use --force to dump anyway",
+                    site.display(),
+                    state
+                )
+            }
+        }
+        Ok(())
+    }
+
+    /// dump the sites of a drip before pouring; sites edited in place are
+    /// only backed up, as pouring updates them in place
+    pub fn dump(&mut self, name: String, drip: &Drip, force: bool) -> anyhow::Result<()> {
+        self.dream(name, drip, force, false)
+    }
+
+    /// dump the sites of a drip for good, also taking back in-place edits
+    pub fn unsync(&mut self, name: String, drip: &Drip, force: bool) -> anyhow::Result<()> {
+        self.dream(name, drip, force, true)
+    }
+
+    fn dream(
+        &mut self, name: String, drip: &Drip, force: bool, retract: bool,
+    ) -> anyhow::Result<()> {
+        Self::check_dumpable(drip, force)?;
         let uid = Uuid::now_v1(&[0, 0, 0, 0, 0, 0]);
         let path = Self::uid_path(&name, uid);
         let mut did_sth = false;
@@ -80,13 +120,37 @@ impl Dreamer {
             }
             Ok(())
         };
-        for Arrow { rel_site: stem, .. } in drip.arrows.iter() {
+        for Arrow {
+            rel_site: stem,
+            src,
+            ..
+        } in drip.arrows.iter()
+        {
             let site = drip.site.join(stem);
-            log::info!("mv {} {}", site.display(), path.join(stem).display());
             crate::utils::path::create_dir_parent(&site)?;
             let site = crate::utils::path::canonicalize(site)?;
-            crate::utils::path::create_dir_parent(&path.join(stem))?;
+            crate::utils::path::create_dir_parent(path.join(stem))?;
             let dump = crate::utils::path::canonicalize(path.join(stem))?;
+            if src.in_place() {
+                if !site.is_file() {
+                    continue;
+                }
+                log::info!("cp {} {}", site.display(), dump.display());
+                do_it()?;
+                std::fs::copy(&site, &dump).map_err(|e| {
+                    anyhow::anyhow!(
+                        "failed to copy {} to {}: {}",
+                        site.display(),
+                        dump.display(),
+                        e
+                    )
+                })?;
+                if retract {
+                    Self::retract(&name, src, &site)?;
+                }
+                continue;
+            }
+            log::info!("mv {} {}", site.display(), dump.display());
             if site.is_symlink() {
                 std::fs::remove_file(&site).map_err(|e| {
                     anyhow::anyhow!("failed to remove symlink {}: {}", site.display(), e)
@@ -117,6 +181,16 @@ impl Dreamer {
         Ok(())
     }
 
+    /// take back what an in-place arrow has written into site
+    fn retract(name: &str, src: &ArrowSrc, site: &Path) -> anyhow::Result<()> {
+        if let ArrowSrc::Block(block) = src {
+            log::info!("strip block of <{}> from {}", name, site.display());
+            let content = std::fs::read_to_string(site)?;
+            std::fs::write(site, block.strip(name, &content))?;
+        }
+        Ok(())
+    }
+
     pub fn write_index(&self) -> anyhow::Result<()> {
         let content = serde_json::to_string(&self)
             .map_err(|e| anyhow::anyhow!("failed to serialize index.json: {}", e))?;
@@ -1,5 +1,8 @@
 use serde::{Deserialize, Serialize};
-use std::{fmt::Display, path::PathBuf};
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 #[serde(deny_unknown_fields)]
@@ -19,6 +22,46 @@ pub struct Arrow {
     #[serde(rename = "site")]
     pub rel_site: PathBuf,
     pub src: ArrowSrc,
+    /// only pour when the condition holds on the machine
+    #[serde(rename = "if", default, skip_serializing_if = "Option::is_none")]
+    pub cond: Option<Cond>,
+}
+
+/// a precondition on the machine, checked when building the drugstore
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Cond {
+    /// a program that must be found on `PATH`
+    pub command: Option<String>,
+    /// a path that must exist
+    pub exists: Option<PathBuf>,
+}
+
+impl Cond {
+    /// `Err` with the failing condition if it doesn't hold; `assume` answers
+    /// for a machine other than the running one, where it can't be checked
+    pub fn check_as(&self, assume: Option<bool>) -> Result<(), String> {
+        match assume {
+            | None => self.check(),
+            | Some(true) => Ok(()),
+            | Some(false) => Err(format!("{} assumed not to hold", self)),
+        }
+    }
+
+    /// `Err` with the failing condition if it doesn't hold
+    pub fn check(&self) -> Result<(), String> {
+        if let Some(command) = &self.command
+            && crate::utils::path::which(command).is_none()
+        {
+            Err(format!("command `{}` not found", command))?
+        }
+        if let Some(exists) = &self.exists
+            && !crate::utils::path::expand_home(exists).exists()
+        {
+            Err(format!("path `{}` does not exist", exists.display()))?
+        }
+        Ok(())
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -30,6 +73,177 @@ pub enum ArrowSrc {
     Link(PathBuf),
     #[serde(rename = "collector")]
     Collector,
+    /// a `.tar`, `.tar.gz`, `.tgz` or `.zip` file in repo, extracted onto site
+    #[serde(rename = "archive")]
+    Archive(PathBuf),
+    /// stdout of a program run in repo, written onto site
+    #[serde(rename = "cmd")]
+    Cmd(Generator),
+    /// fragments in repo concatenated onto site; fragments of the same site
+    /// are collected from all applied drips, in drip order
+    #[serde(rename = "concat")]
+    Concat(Concat),
+    /// a delimited block inside a site file that is otherwise left untouched
+    #[serde(rename = "block")]
+    Block(Block),
+    /// a json or toml fragment in repo, deep-merged into the site file
+    #[serde(rename = "merge")]
+    Merge(PathBuf),
+}
+
+impl ArrowSrc {
+    /// whether the arrow edits its site in place instead of owning it
+    pub fn in_place(&self) -> bool {
+        matches!(self, ArrowSrc::Block(_) | ArrowSrc::Merge(_))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Generator {
+    pub prog: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// files or directories relative to drip root in repo; the program is
+    /// only run again when any of them changes
+    #[serde(default)]
+    pub inputs: Vec<PathBuf>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Concat {
+    /// fragments relative to drip root in repo
+    pub parts: Vec<PathBuf>,
+    /// written between two fragments
+    pub sep: Option<String>,
+    /// written before each fragment, with `{}` replaced by the fragment path
+    pub header: Option<String>,
+}
+
+impl Concat {
+    /// take in the fragments of the same site from a later drip
+    pub fn absorb(&mut self, other: Concat) -> anyhow::Result<()> {
+        self.parts.extend(other.parts);
+        self.sep = match (other.sep, self.sep.take()) {
+            | (Some(_), Some(_)) => Err(anyhow::anyhow!("sep set multiple times"))?,
+            | (new @ Some(_), _) => new,
+            | (None, old) => old,
+        };
+        self.header = match (other.header, self.header.take()) {
+            | (Some(_), Some(_)) => Err(anyhow::anyhow!("header set multiple times"))?,
+            | (new @ Some(_), _) => new,
+            | (None, old) => old,
+        };
+        Ok(())
+    }
+
+    /// the content to be written onto site
+    pub fn assemble(&self, root: &Path) -> anyhow::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        for (i, part) in self.parts.iter().enumerate() {
+            if i > 0
+                && let Some(sep) = &self.sep
+            {
+                buf.extend(sep.as_bytes());
+            }
+            if let Some(header) = &self.header {
+                buf.extend(header.replace("{}", &part.to_string_lossy()).as_bytes());
+                buf.push(b'\n');
+            }
+            let path = root.join(part);
+            let content = std::fs::read(&path)
+                .map_err(|e| anyhow::anyhow!("failed to read {}: {}", path.display(), e))?;
+            buf.extend(content);
+        }
+        Ok(buf)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Block {
+    /// content of the block, relative to drip root in repo
+    pub file: PathBuf,
+    /// line comment leader of the site file
+    #[serde(default = "Block::default_comment")]
+    pub comment: String,
+}
+
+impl Block {
+    fn default_comment() -> String {
+        "#".to_owned()
+    }
+    fn begin(&self, pill: &str) -> String {
+        format!("{} >>> underdose:{} >>>", self.comment, pill)
+    }
+    fn end(&self) -> String {
+        format!("{} <<< underdose <<<", self.comment)
+    }
+
+    /// the block as it should appear in site
+    pub fn render(&self, pill: &str, content: &str) -> String {
+        let mut block = self.begin(pill);
+        block.push('\n');
+        block.push_str(content);
+        if !content.is_empty() && !content.ends_with('\n') {
+            block.push('\n');
+        }
+        block.push_str(&self.end());
+        block.push('\n');
+        block
+    }
+
+    /// line range of the block of `pill` in `site`, end exclusive
+    fn locate(&self, pill: &str, lines: &[&str]) -> Option<(usize, usize)> {
+        let (begin, end) = (self.begin(pill), self.end());
+        let start = lines.iter().position(|line| line.trim_end() == begin)?;
+        let stop = lines[start..]
+            .iter()
+            .position(|line| line.trim_end() == end)?;
+        Some((start, start + stop + 1))
+    }
+
+    /// the block of `pill` in `site`, if any
+    pub fn find(&self, pill: &str, site: &str) -> Option<String> {
+        let lines: Vec<_> = site.split_inclusive('\n').collect();
+        let (start, stop) = self.locate(pill, &lines)?;
+        Some(lines[start..stop].concat())
+    }
+
+    /// `site` with the block of `pill` updated in place, or appended
+    pub fn splice(&self, pill: &str, site: &str, block: &str) -> String {
+        let lines: Vec<_> = site.split_inclusive('\n').collect();
+        match self.locate(pill, &lines) {
+            | Some((start, stop)) => {
+                [&lines[..start].concat(), block, &lines[stop..].concat()].concat()
+            }
+            | None if site.is_empty() || site.ends_with('\n') => [site, block].concat(),
+            | None => [site, "\n", block].concat(),
+        }
+    }
+
+    /// `site` with the block of `pill` removed
+    pub fn strip(&self, pill: &str, site: &str) -> String {
+        let lines: Vec<_> = site.split_inclusive('\n').collect();
+        match self.locate(pill, &lines) {
+            | Some((start, stop)) => [lines[..start].concat(), lines[stop..].concat()].concat(),
+            | None => site.to_owned(),
+        }
+    }
+}
+
+impl Display for Cond {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(command) = &self.command {
+            parts.push(format!("command `{}`", command));
+        }
+        if let Some(exists) = &self.exists {
+            parts.push(format!("path `{}`", exists.display()));
+        }
+        write!(f, "{}", parts.join(" and "))
+    }
 }
 
 impl Display for ArrowSrc {
@@ -38,6 +252,53 @@ impl Display for ArrowSrc {
             | ArrowSrc::Git(remote) => write!(f, "git({})", remote),
             | ArrowSrc::Link(repo) => write!(f, "ln({})", repo.display()),
             | ArrowSrc::Collector => write!(f, "collector"),
+            | ArrowSrc::Archive(repo) => write!(f, "archive({})", repo.display()),
+            | ArrowSrc::Cmd(Generator { prog, args, .. }) if args.is_empty() => {
+                write!(f, "cmd({})", prog)
+            }
+            | ArrowSrc::Cmd(Generator { prog, args, .. }) => {
+                write!(f, "cmd({} {})", prog, args.join(" "))
+            }
+            | ArrowSrc::Concat(Concat { parts, .. }) => {
+                let parts = parts.iter().map(|p| p.display().to_string());
+                write!(f, "concat({})", parts.collect::<Vec<_>>().join(", "))
+            }
+            | ArrowSrc::Block(Block { file, .. }) => write!(f, "block({})", file.display()),
+            | ArrowSrc::Merge(repo) => write!(f, "merge({})", repo.display()),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_roundtrip() {
+        let block = Block {
+            file: PathBuf::from("rc"),
+            comment: "#".to_owned(),
+        };
+        let site = "export A=1\nexport B=2";
+        let rendered = block.render("bash", "alias ll='ls -l'");
+        let poured = block.splice("bash", site, &rendered);
+        assert_eq!(block.find("bash", &poured), Some(rendered));
+
+        // updated in place, leaving the rest untouched
+        let poured = format!("{}export C=3\n", poured);
+        let rendered = block.render("bash", "alias la='ls -a'\n");
+        let updated = block.splice("bash", &poured, &rendered);
+        assert_eq!(
+            updated,
+            "export A=1\nexport B=2\n\
+             # >>> underdose:bash >>>\nalias la='ls -a'\n# <<< underdose <<<\n\
+             export C=3\n"
+        );
+
+        assert_eq!(
+            block.strip("bash", &updated),
+            "export A=1\nexport B=2\nexport C=3\n"
+        );
+        assert_eq!(block.strip("zsh", &updated), updated);
+    }
+}
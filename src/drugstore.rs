@@ -1,51 +1,140 @@
+use crate::env::{EnvMap, EnvSet, Expr, Facts};
+use crate::schedule::{Node, Schedule};
 use crate::utils::conf::TomlStr;
-use crate::{Arrow, Drip, Machine};
+use crate::{Arrow, ArrowSrc, Cond, Drip, Generator, Ledger, Machine};
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 use std::{
     collections::{HashMap, HashSet},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
 
 #[derive(Debug)]
 pub struct Drugstore {
     pub env: EnvSet,
+    /// tags added from facts, each with the fact it comes from
+    pub detected: IndexMap<String, String>,
+    /// `run` probes of the env by tag, as commands to be trusted
+    pub probes: IndexMap<String, Command>,
     pub cmds: IndexMap<String, Command>,
     pub pills: IndexMap<String, Drip>,
+    /// names of commands and pills selected together by `@group`
+    pub groups: IndexMap<String, Vec<String>>,
+    /// hooks of the pills, if any
+    pub hooks: IndexMap<String, Hooks>,
+    /// programs of the `src.cmd` arrows of the pills, if any, to be trusted
+    /// like commands
+    pub generators: IndexMap<String, Vec<Command>>,
+    /// the order to run commands and pour pills in
+    pub schedule: Schedule,
+    /// drips and arrows left out as their conditions failed
+    pub skipped: Vec<Skipped>,
+    /// drips and arrows kept with conditions that can't be checked, as the
+    /// machine is not the running one
+    pub assumed: Vec<Assumed>,
+    /// pills left out as their drips don't fit together, with the reason
+    pub ignored: IndexMap<String, String>,
+    /// how the drips of each pill were resolved on the machine
+    pub traces: IndexMap<String, PillTrace>,
+    /// files read besides `Drugstore.toml`, relative to the repo
+    pub files: Vec<PathBuf>,
+    /// what the overrides in `Underdose.toml` changed of each pill
+    pub overridden: IndexMap<String, Vec<String>>,
+    /// sites poured twice, or inside one another; nothing is synced if any
+    pub overlaps: Vec<Overlap>,
+    /// required commands or pills not on this machine, by the step requiring
+    /// them; such a step can't be synced
+    pub unmet: IndexMap<Node, Vec<String>>,
 }
 
-/// a map of name -> upward dependencies, up to the root
+/// how a pill was resolved on the machine
 #[derive(Debug)]
-pub struct EnvMap {
-    pub map: HashMap<String, HashSet<String>>,
+pub struct PillTrace {
+    pub drips: Vec<DripTrace>,
+    /// left out by `disable = true` in `Underdose.toml`
+    pub disabled: bool,
 }
 
-impl EnvMap {
-    pub fn resolve(&self, machine: &Machine) -> anyhow::Result<EnvSet> {
-        let mut res = HashSet::new();
-        for tag in &machine.env {
-            let deps = self.map.get(tag).ok_or_else(|| {
-                anyhow::anyhow!("tag {} is not defined in env dependency map", tag)
-            })?;
-            res.insert(tag.to_owned());
-            res.extend(deps.to_owned());
-        }
-        Ok(EnvSet { set: res })
+/// a drip as declared, and whether it applied on the machine
+#[derive(Debug)]
+pub struct DripTrace {
+    pub tags: Vec<Expr>,
+    /// `None` if applied, or why not
+    pub skipped: Option<String>,
+    pub site: Option<PathBuf>,
+    pub repo: Option<PathBuf>,
+    pub arrows: Vec<Arrow>,
+}
+
+/// two arrows whose sites are the same or nested
+#[derive(Debug)]
+pub struct Overlap {
+    /// the pills pouring them, the same one twice if it pours both
+    pub pills: [String; 2],
+    what: String,
+}
+
+impl Overlap {
+    /// whether any of `nodes` pours one of the sites
+    pub fn involves(&self, nodes: &[Node]) -> bool {
+        (self.pills.iter()).any(|pill| nodes.contains(&Node::Pill(pill.clone())))
     }
 }
 
-/// a set of machine possessed envs
+impl std::fmt::Display for Overlap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.what)
+    }
+}
+
+/// a drip or an arrow whose env matched but whose condition failed
 #[derive(Debug)]
-pub struct EnvSet {
-    pub set: HashSet<String>,
+pub struct Skipped {
+    pub pill: String,
+    /// `None` if the whole drip is skipped
+    pub arrow: Option<Arrow>,
+    pub reason: String,
 }
 
-impl EnvSet {
-    pub fn check(&self, tag: &str) -> bool {
-        self.set.contains(tag)
+impl std::fmt::Display for Skipped {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.arrow {
+            | Some(arrow) => write!(
+                f,
+                "<{}> skipped {} {}: {}",
+                self.pill,
+                arrow.rel_site.display(),
+                arrow.src,
+                self.reason
+            ),
+            | None => write!(f, "<{}> skipped drip: {}", self.pill, self.reason),
+        }
     }
-    pub fn check_all(&self, tags: &HashSet<String>) -> bool {
-        tags.iter().all(|tag| self.check(tag))
+}
+
+/// a drip or an arrow whose condition is taken to hold on another machine
+#[derive(Debug)]
+pub struct Assumed {
+    pub pill: String,
+    /// `None` for the whole drip
+    pub arrow: Option<Arrow>,
+    pub cond: Cond,
+}
+
+impl std::fmt::Display for Assumed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.arrow {
+            | Some(arrow) => write!(
+                f,
+                "<{}> assumes {} for {} {}",
+                self.pill,
+                self.cond,
+                arrow.rel_site.display(),
+                arrow.src
+            ),
+            | None => write!(f, "<{}> assumes {} for a drip", self.pill, self.cond),
+        }
     }
 }
 
@@ -54,12 +143,268 @@ pub struct Command {
     pub name: String,
     pub prog: String,
     pub args: Vec<String>,
+    /// where to run, the drugstore root by default
+    pub cwd: PathBuf,
+    /// environment variables added, including the `UD_*` ones but `UD_ENV`,
+    /// which is set as the command runs
+    pub vars: IndexMap<String, String>,
+    pub timeout: Option<Duration>,
+    pub guard: Guard,
+}
+
+/// what commands get to know about the machine
+struct Context<'a> {
+    root: &'a Path,
+    vars: IndexMap<String, String>,
+    /// `${name}` in site and repo paths, besides environment variables
+    path_vars: IndexMap<String, String>,
+}
+
+impl<'a> Context<'a> {
+    fn new(machine: &'a Machine, user_vars: IndexMap<String, String>) -> anyhow::Result<Self> {
+        let vars = IndexMap::from([
+            ("UD_MACHINE".to_owned(), machine.name.clone()),
+            (
+                "UD_DRUGSTORE".to_owned(),
+                machine.local.to_string_lossy().into_owned(),
+            ),
+        ]);
+        let mut ctx = Context {
+            root: &machine.local,
+            vars,
+            path_vars: IndexMap::new(),
+        };
+        if machine.synthetic {
+            // the directories of another machine are not known here, so they
+            // are left for it to resolve, but for home
+            ctx.path_vars.insert("home".to_owned(), "~".to_owned());
+            for name in ["config", "data", "cache"] {
+                ctx.path_vars
+                    .insert(name.to_owned(), format!("${{{}}}", name));
+            }
+        } else if let Some(dirs) = directories::BaseDirs::new() {
+            let dir = |path: &Path| path.to_string_lossy().into_owned();
+            ctx.path_vars
+                .insert("home".to_owned(), dir(dirs.home_dir()));
+            ctx.path_vars
+                .insert("config".to_owned(), dir(dirs.config_dir()));
+            ctx.path_vars
+                .insert("data".to_owned(), dir(dirs.data_dir()));
+            ctx.path_vars
+                .insert("cache".to_owned(), dir(dirs.cache_dir()));
+        }
+        ctx.path_vars
+            .insert("machine".to_owned(), machine.name.clone());
+        // user variables may refer to the ones before them
+        for (name, value) in user_vars {
+            if ctx.path_vars.contains_key(&name) {
+                anyhow::bail!("var {} is already defined", name);
+            }
+            let value = ctx
+                .interpolate(&value)
+                .map_err(|e| anyhow::anyhow!("in var {}: {}", name, e))?;
+            ctx.path_vars.insert(name, value);
+        }
+        Ok(ctx)
+    }
+
+    fn interpolate(&self, s: &str) -> anyhow::Result<String> {
+        let lookup = |name: &str| match self.path_vars.get(name) {
+            | Some(value) => Ok(Some(value.clone())),
+            | None => match std::env::var(name) {
+                | Ok(value) => Ok(Some(value)),
+                | Err(_) => Err("not defined"),
+            },
+        };
+        let res = shellexpand::env_with_context(s, lookup)
+            .map_err(|e| anyhow::anyhow!("variable {} is {}", e.var_name, e.cause))?;
+        Ok(res.into_owned())
+    }
+
+    /// `path` with the variables in it replaced
+    fn expand(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        match path.to_str() {
+            | Some(s) if s.contains('$') => Ok(PathBuf::from(self.interpolate(s)?)),
+            | _ => Ok(path.to_owned()),
+        }
+    }
+
+    fn command(
+        &self, name: String, pill: Option<&str>, exec: parse::Exec, guard: Guard,
+    ) -> anyhow::Result<Command> {
+        let (prog, args) = match (exec.prog, exec.shell) {
+            | (Some(prog), None) => (prog, exec.args),
+            | (None, Some(shell)) if exec.args.is_empty() => {
+                if cfg!(windows) {
+                    ("cmd".to_owned(), vec!["/C".to_owned(), shell])
+                } else {
+                    ("sh".to_owned(), vec!["-c".to_owned(), shell])
+                }
+            }
+            | (None, Some(_)) => anyhow::bail!("command <{}> takes no `args` with `shell`", name),
+            | _ => anyhow::bail!("command <{}> needs exactly one of `prog` and `shell`", name),
+        };
+        let cwd = match exec.cwd {
+            | Some(cwd) => self.root.join(crate::utils::path::expand_home(cwd)),
+            | None => self.root.to_owned(),
+        };
+        let mut vars = self.vars.clone();
+        if let Some(pill) = pill {
+            vars.insert("UD_PILL".to_owned(), pill.to_owned());
+        }
+        vars.extend(exec.vars);
+        Ok(Command {
+            name,
+            prog,
+            args,
+            cwd,
+            vars,
+            timeout: exec.timeout.map(Duration::from_secs),
+            guard,
+        })
+    }
+}
+
+/// conditions under which a command is not run
+#[derive(Debug, Default, Clone)]
+pub struct Guard {
+    pub creates: Option<PathBuf>,
+    pub unless: Option<Vec<String>>,
+    pub once: bool,
+}
+
+impl Command {
+    /// why the command is skipped, if it is
+    pub fn skipped(&self, ledger: &Ledger, env: &EnvSet) -> anyhow::Result<Option<String>> {
+        self.skipped_as(ledger, Some(env))
+    }
+
+    /// as `skipped`, but `unless` is only run if given the env to run it on
+    pub fn skipped_as(
+        &self, ledger: &Ledger, env: Option<&EnvSet>,
+    ) -> anyhow::Result<Option<String>> {
+        let Guard {
+            creates,
+            unless,
+            once,
+        } = &self.guard;
+        if *once && ledger.once.contains(&self.name) {
+            return Ok(Some("already run once".to_owned()));
+        }
+        if let Some(creates) = creates
+            && crate::utils::path::expand_home(creates).exists()
+        {
+            return Ok(Some(format!("`{}` exists", creates.display())));
+        }
+        if let Some(unless) = unless
+            && let Some(env) = env
+            && let [prog, args @ ..] = unless.as_slice()
+        {
+            let status = std::process::Command::new(prog)
+                .args(args)
+                .current_dir(&self.cwd)
+                .env("UD_ENV", env.all_tags().join(","))
+                .envs(&self.vars)
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null())
+                .status()
+                .map_err(|e| anyhow::anyhow!("failed to run `unless` of <{}>: {}", self.name, e))?;
+            if status.success() {
+                return Ok(Some(format!("`{}` succeeded", unless.join(" "))));
+            }
+        }
+        Ok(None)
+    }
+
+    /// hash of everything the command would execute, so that a change to it
+    /// has to be trusted again; the `UD_*` variables describe the machine and
+    /// are left out
+    pub fn fingerprint(&self) -> String {
+        let vars: IndexMap<_, _> = (self.vars.iter())
+            .filter(|(name, _)| !name.starts_with("UD_"))
+            .collect();
+        let exec = (&self.prog, &self.args, &self.cwd, &vars, &self.guard.unless);
+        let exec = serde_json::to_string(&exec).expect("command should serialize");
+        crate::stamps::digest([exec.as_bytes()])
+    }
+
+    pub fn run(&self, env: &EnvSet) -> anyhow::Result<()> {
+        log::info!(
+            "running command <{}> :: {} {}",
+            self.name,
+            self.prog,
+            self.args.join(" ")
+        );
+        let mut child = std::process::Command::new(&self.prog)
+            .args(&self.args)
+            .current_dir(&self.cwd)
+            .env("UD_ENV", env.all_tags().join(","))
+            .envs(&self.vars)
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("failed to run command <{}>: {}", self.name, e))?;
+        let status = match self.timeout {
+            | None => child.wait()?,
+            | Some(timeout) => {
+                let start = Instant::now();
+                loop {
+                    if let Some(status) = child.try_wait()? {
+                        break status;
+                    }
+                    if start.elapsed() > timeout {
+                        child.kill()?;
+                        child.wait()?;
+                        anyhow::bail!(
+                            "command <{}> timed out after {}s",
+                            self.name,
+                            timeout.as_secs()
+                        );
+                    }
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+            }
+        };
+        if !status.success() {
+            anyhow::bail!("command failed: {}", self.name);
+        }
+        Ok(())
+    }
+}
+
+/// commands run around pouring a pill
+#[derive(Debug, Default)]
+pub struct Hooks {
+    pub pre: Vec<Command>,
+    pub post: Vec<Command>,
+}
+
+impl Hooks {
+    fn new(pill: &str, hooks: parse::Hooks, ctx: &Context) -> anyhow::Result<Self> {
+        // named apart so that each is trusted on its own
+        let named = |stage: &str, hooks: Vec<parse::Exec>| {
+            (hooks.into_iter().enumerate())
+                .map(|(i, hook)| {
+                    let name = format!("{}.{}.{}", pill, stage, i + 1);
+                    ctx.command(name, Some(pill), hook, Guard::default())
+                })
+                .collect::<anyhow::Result<_>>()
+        };
+        Ok(Hooks {
+            pre: named("pre", hooks.pre)?,
+            post: named("post", hooks.post)?,
+        })
+    }
+    pub fn is_empty(&self) -> bool {
+        self.pre.is_empty() && self.post.is_empty()
+    }
 }
 
 #[derive(Debug)]
 pub struct Pill {
     pub name: String,
     pub drip: Drip,
+    pub hooks: parse::Hooks,
+    pub skipped: Vec<Skipped>,
+    pub assumed: Vec<Assumed>,
 }
 
 impl Pill {
@@ -75,37 +420,248 @@ mod parse {
     #[serde(deny_unknown_fields)]
     pub struct Drugstore {
         pub env: toml::Value,
+        /// fact -> value -> tag the machine gets when the fact has the value
+        #[serde(default)]
+        pub detect: IndexMap<String, IndexMap<String, String>>,
+        /// variables for site and repo paths
+        #[serde(default)]
+        pub var: IndexMap<String, String>,
+        /// globs of files relative to the repo, each with more `cmd` and `pill`
+        #[serde(default)]
+        pub include: Vec<String>,
         #[serde(default)]
         pub cmd: Vec<Command>,
         #[serde(default)]
         pub pill: Vec<Pill>,
+        #[serde(default)]
+        pub group: IndexMap<String, Group>,
+        /// machines sharing the drugstore, checked by `ud check`
+        #[serde(default)]
+        pub machine: IndexMap<String, crate::check::KnownMachine>,
         pub tutorial: Option<()>,
     }
 
+    impl Drugstore {
+        /// pull in the files matched by `include` and the `pill.toml` in each
+        /// top-level directory of `repo`, refusing names defined twice; returns
+        /// the files read, relative to `repo`
+        pub fn gather(&mut self, repo: &Path) -> anyhow::Result<Vec<PathBuf>> {
+            let main = PathBuf::from("Drugstore.toml");
+            let mut cmds = IndexMap::new();
+            let mut pills = IndexMap::new();
+            for cmd in self.cmd.iter() {
+                define(&mut cmds, "command", &cmd.name, &main)?;
+            }
+            for pill in self.pill.iter() {
+                define(&mut pills, "pill", &pill.name, &main)?;
+            }
+
+            let mut files = Vec::new();
+            let root = glob::Pattern::escape(&repo.to_string_lossy());
+            for pattern in self.include.iter() {
+                let matched = glob::glob(&Path::new(&root).join(pattern).to_string_lossy())
+                    .map_err(|e| anyhow::anyhow!("invalid include `{}`: {}", pattern, e))?
+                    .collect::<Result<Vec<_>, _>>()?;
+                if matched.is_empty() {
+                    log::warn!("include `{}` matches no file", pattern);
+                }
+                for path in matched {
+                    let rel = path.strip_prefix(repo).unwrap_or(&path).to_owned();
+                    let fragment: Fragment = read(&path, &rel)?;
+                    for cmd in fragment.cmd {
+                        define(&mut cmds, "command", &cmd.name, &rel)?;
+                        self.cmd.push(cmd);
+                    }
+                    for mut pill in fragment.pill {
+                        define(&mut pills, "pill", &pill.name, &rel)?;
+                        pill.file = Some(rel.clone());
+                        self.pill.push(pill);
+                    }
+                    files.push(rel);
+                }
+            }
+
+            let mut dirs = Vec::new();
+            for entry in std::fs::read_dir(repo).into_iter().flatten() {
+                let entry = entry?;
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if entry.file_type()?.is_dir() && !name.starts_with('.') {
+                    dirs.push(name);
+                }
+            }
+            dirs.sort();
+            for dir in dirs {
+                let rel = Path::new(&dir).join("pill.toml");
+                let path = repo.join(&rel);
+                if !path.is_file() || files.contains(&rel) {
+                    continue;
+                }
+                // the pill is named after its directory unless it says otherwise
+                let mut table: toml::Table = read(&path, &rel)?;
+                table
+                    .entry("name")
+                    .or_insert_with(|| toml::Value::String(dir.clone()));
+                let mut pill: Pill = (table.try_into())
+                    .map_err(|e| anyhow::anyhow!("in {}: {}", rel.display(), e))?;
+                define(&mut pills, "pill", &pill.name, &rel)?;
+                pill.file = Some(rel.clone());
+                pill.dir = Some(PathBuf::from(&dir));
+                self.pill.push(pill);
+                files.push(rel);
+            }
+            Ok(files)
+        }
+    }
+
+    /// parse a manifest file, with `rel` in errors
+    fn read<T: serde::de::DeserializeOwned>(path: &Path, rel: &Path) -> anyhow::Result<T> {
+        let buf = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("failed to read {}: {}", rel.display(), e))?;
+        toml::from_str(&buf).map_err(|e| anyhow::anyhow!("in {}: {}", rel.display(), e))
+    }
+
+    /// remember where `name` is defined, refusing it if it already is
+    fn define(
+        defined: &mut IndexMap<String, PathBuf>, kind: &str, name: &str, file: &Path,
+    ) -> anyhow::Result<()> {
+        if let Some(first) = defined.get(name) {
+            if first == file {
+                anyhow::bail!("{} <{}> is defined twice in {}", kind, name, file.display());
+            }
+            anyhow::bail!(
+                "{} <{}> is defined in both {} and {}",
+                kind,
+                name,
+                first.display(),
+                file.display()
+            );
+        }
+        defined.insert(name.to_owned(), file.to_owned());
+        Ok(())
+    }
+
+    /// commands and pills kept in a file of their own
+    #[derive(Serialize, Deserialize, Debug)]
+    #[serde(deny_unknown_fields)]
+    pub struct Fragment {
+        #[serde(default)]
+        pub cmd: Vec<Command>,
+        #[serde(default)]
+        pub pill: Vec<Pill>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    #[serde(deny_unknown_fields)]
+    pub struct Group {
+        /// commands or pills, which are left out on machines they're not on
+        pub pills: Vec<String>,
+    }
+
     #[derive(Serialize, Deserialize, Debug)]
     #[serde(deny_unknown_fields)]
     pub struct Command {
         pub name: String,
+        /// env expressions that must all hold
         #[serde(alias = "env", default)]
         pub tags: HashSet<String>,
-        pub prog: String,
+        pub prog: Option<String>,
+        #[serde(default)]
         pub args: Vec<String>,
+        /// run by the system shell instead of `prog`
+        pub shell: Option<String>,
+        /// relative to the drugstore root
+        pub cwd: Option<PathBuf>,
+        /// environment variables to add
+        #[serde(default)]
+        pub vars: IndexMap<String, String>,
+        /// in seconds
+        pub timeout: Option<u64>,
+        /// run after these commands or pills, if they run at all
+        #[serde(default)]
+        pub after: Vec<String>,
+        /// run after these commands or pills, pulling them in
+        #[serde(default)]
+        pub requires: Vec<String>,
+        /// skip if the path exists
+        pub creates: Option<PathBuf>,
+        /// skip if this program exits successfully
+        pub unless: Option<Vec<String>>,
+        /// skip once succeeded on this machine
+        #[serde(default)]
+        pub once: bool,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Default)]
+    #[serde(deny_unknown_fields)]
+    pub struct Hooks {
+        /// run before pouring the pill
+        #[serde(default)]
+        pub pre: Vec<Exec>,
+        /// run after pouring the pill
+        #[serde(default)]
+        pub post: Vec<Exec>,
+    }
+
+    impl Hooks {
+        pub fn extend(&mut self, hooks: Hooks) {
+            self.pre.extend(hooks.pre);
+            self.post.extend(hooks.post);
+        }
+    }
+
+    /// a program to run, as in hooks
+    #[derive(Serialize, Deserialize, Debug)]
+    #[serde(deny_unknown_fields)]
+    pub struct Exec {
+        pub prog: Option<String>,
+        #[serde(default)]
+        pub args: Vec<String>,
+        /// run by the system shell instead of `prog`
+        pub shell: Option<String>,
+        /// relative to the drugstore root
+        pub cwd: Option<PathBuf>,
+        /// environment variables to add
+        #[serde(default)]
+        pub vars: IndexMap<String, String>,
+        /// in seconds
+        pub timeout: Option<u64>,
     }
 
     #[derive(Serialize, Deserialize, Debug)]
     #[serde(deny_unknown_fields)]
     pub struct Pill {
         pub name: String,
+        /// pour after these commands or pills, if they run at all
+        #[serde(default)]
+        pub after: Vec<String>,
+        /// pour after these commands or pills, pulling them in
+        #[serde(default)]
+        pub requires: Vec<String>,
+        /// run only when the pill has changed
+        #[serde(default)]
+        pub hook: Hooks,
         #[serde(alias = "drip")]
         pub drips: Vec<Drip>,
+        /// the file the pill is defined in, if not `Drugstore.toml`
+        #[serde(skip)]
+        pub file: Option<PathBuf>,
+        /// the directory of its `pill.toml`, which is the default repo
+        #[serde(skip)]
+        pub dir: Option<PathBuf>,
     }
 
     #[derive(Serialize, Deserialize, Debug)]
     #[serde(deny_unknown_fields)]
     pub struct Drip {
-        /// `tags` are resolved to trivial form during parsing
+        /// each tag is an expression like `!mac` or `linux|bsd`, and all must hold
         #[serde(alias = "env", default)]
         pub tags: HashSet<String>,
+        /// only apply when the condition holds on the machine
+        #[serde(rename = "if", default)]
+        pub cond: Option<Cond>,
+        /// added to the hooks of the pill
+        #[serde(default)]
+        pub hook: Hooks,
         /// where the root of site is, globally
         pub site: Option<PathBuf>,
         /// where the root of drip is, relative to repo root
@@ -120,100 +676,580 @@ impl TryFrom<(TomlStr<'_>, &Machine)> for Drugstore {
     type Error = anyhow::Error;
 
     fn try_from((buf, machine): (TomlStr<'_>, &Machine)) -> anyhow::Result<Self> {
-        let conf: parse::Drugstore = toml::from_str(buf.as_str())?;
-        (conf, machine).try_into()
+        Self::load(buf, machine, false, &Probing::default())
     }
 }
 
-impl TryFrom<(parse::Drugstore, &Machine)> for Drugstore {
-    type Error = anyhow::Error;
+/// which steps may run the probes of the env they need
+#[derive(Debug, Default)]
+pub struct Probing<'a> {
+    /// names as given to `sync`, everything if empty
+    pub names: &'a [String],
+    pub skip: &'a [String],
+    /// approves `run` probes; only `exists` ones are checked without it
+    pub ledger: Option<&'a Ledger>,
+}
+
+impl Drugstore {
+    /// `lenient` downgrades undefined tags in drips and commands to warnings
+    pub fn load(
+        buf: TomlStr<'_>, machine: &Machine, lenient: bool, probing: &Probing,
+    ) -> anyhow::Result<Self> {
+        let mut conf: parse::Drugstore = toml::from_str(buf.as_str())?;
+        let files = conf.gather(&machine.local)?;
+        let mut store = Self::build(conf, machine, lenient, probing)?;
+        store.files = files;
+        Ok(store)
+    }
 
-    fn try_from((store, machine): (parse::Drugstore, &Machine)) -> anyhow::Result<Self> {
-        if let Some(_) = store.tutorial {
+    fn build(
+        store: parse::Drugstore, machine: &Machine, lenient: bool, probing: &Probing,
+    ) -> anyhow::Result<Self> {
+        if store.tutorial.is_some() {
             Err(anyhow::anyhow!("tutorial has not been completed yet"))?;
         }
-        let mut map = HashMap::new();
-        fn register_env<'e>(
-            env: &mut HashMap<String, HashSet<String>>, worklist: &mut Vec<&'e str>,
-            toml: &'e toml::Value,
-        ) {
-            fn register<'e>(
-                env: &mut HashMap<String, HashSet<String>>, worklist: &'e [&'e str], s: &'e str,
-            ) {
-                env.entry(s.to_owned())
-                    .or_default()
-                    .extend(worklist.iter().map(|s| s.to_string()))
-            }
-            if let Some(s) = toml.as_str() {
-                register(env, worklist, s);
-            } else if let Some(m) = toml.as_table() {
-                for (k, v) in m {
-                    register(env, worklist, k);
-                    worklist.push(k);
-                    register_env(env, worklist, v);
-                    worklist.pop();
+        let env_map = EnvMap::new(&store.env)?;
+        for (name, known) in store.machine.iter() {
+            for tag in known.env.iter() {
+                env_map
+                    .find(tag)
+                    .map_err(|e| anyhow::anyhow!("in machine {}: {}", name, e))?;
+            }
+        }
+        // steps that may run probes
+        let reach = reach(&store, probing.names, probing.skip);
+        let mut detected = IndexMap::new();
+        for (fact, values) in store.detect {
+            if !Facts::NAMES.contains(&fact.as_str()) {
+                anyhow::bail!(
+                    "unknown fact `{}` in detect, expected one of {}",
+                    fact,
+                    Facts::NAMES.join(", ")
+                );
+            }
+            for (value, tag) in values {
+                let tag = (env_map.find(&tag))
+                    .map_err(|e| anyhow::anyhow!("in detect `{} = {}`: {}", fact, value, e))?;
+                if machine.facts.get(&fact) == Some(value.as_str()) {
+                    detected.insert(tag, format!("{} = {}", fact, value));
                 }
             }
         }
-        register_env(&mut map, &mut Vec::new(), &store.env);
-        let env = EnvMap { map }.resolve(machine)?;
+        // probes are run once a step in reach needs them, and only on the
+        // running system; a `run` probe has to be trusted like a command
+        let mut env_probes = IndexMap::new();
+        let mut probes = IndexMap::new();
+        let mut allowed = HashSet::new();
+        for (tag, probe) in env_map.probes().into_iter().filter(|_| !machine.synthetic) {
+            if let Some([prog, args @ ..]) = probe.run.as_deref() {
+                let cmd = Command {
+                    name: format!("env.{}.probe", tag),
+                    prog: prog.clone(),
+                    args: args.to_vec(),
+                    cwd: machine.local.clone(),
+                    vars: IndexMap::new(),
+                    timeout: None,
+                    guard: Guard::default(),
+                };
+                if probing.ledger.is_some_and(|ledger| ledger.trusts(&cmd)) {
+                    allowed.insert(tag.clone());
+                }
+                probes.insert(tag.clone(), cmd);
+            } else {
+                allowed.insert(tag.clone());
+            }
+            env_probes.insert(tag, probe.clone());
+        }
+        let env = (env_map.resolve(machine.env.iter().chain(detected.keys()))?).with_probes(
+            env_probes,
+            allowed,
+            &machine.local,
+        );
+        let ctx = Context::new(machine, store.var)?;
 
-        let cmds = store
-            .cmd
-            .into_iter()
-            .filter_map(
-                |parse::Command {
-                     name,
-                     tags,
-                     prog,
-                     args,
-                 }| env.check_all(&tags).then(|| (name.clone(), Command { name, prog, args })),
-            )
+        // every name in the store, whether or not on this machine
+        let known: HashSet<String> = (store.cmd.iter().map(|cmd| cmd.name.clone()))
+            .chain(store.pill.iter().map(|pill| pill.name.clone()))
             .collect();
+        let mut deps = Vec::new();
 
-        let mut pills = IndexMap::new();
-        for pill in store.pill {
-            if pills.contains_key(&pill.name) {
-                Err(anyhow::anyhow!("duplicated pill name: {}", pill.name))?
+        let mut cmds = IndexMap::new();
+        for cmd in store.cmd {
+            let tags = Expr::parse_all(&cmd.tags)
+                .map_err(|e| anyhow::anyhow!("invalid env in command <{}>: {}", cmd.name, e))?;
+            check_tags(&env_map, Node::Cmd(cmd.name.clone()), &tags, lenient)?;
+            if !env.check_all_as(&tags, reach.contains(&cmd.name)) {
+                continue;
             }
+            let guard = Guard {
+                creates: cmd.creates,
+                unless: cmd.unless,
+                once: cmd.once,
+            };
+            let exec = parse::Exec {
+                prog: cmd.prog,
+                args: cmd.args,
+                shell: cmd.shell,
+                cwd: cmd.cwd,
+                vars: cmd.vars,
+                timeout: cmd.timeout,
+            };
+            let name = cmd.name.clone();
+            deps.push((Node::Cmd(name.clone()), None, cmd.after, cmd.requires));
+            cmds.insert(name, ctx.command(cmd.name, None, exec, guard)?);
+        }
 
+        let mut pills = IndexMap::new();
+        let mut hooks = IndexMap::new();
+        let mut generators = IndexMap::new();
+        let mut skipped = Vec::new();
+        let mut assumed = Vec::new();
+        let mut ignored = IndexMap::new();
+        let mut traces = IndexMap::new();
+        let mut overridden = IndexMap::new();
+        for name in machine.overrides.keys() {
+            if !store.pill.iter().any(|pill| &pill.name == name) {
+                anyhow::bail!("override of <{}> in Underdose.toml: no such pill", name);
+            }
+        }
+        for pill in store.pill {
             let name = pill.name.clone();
-            match DripApplyIncr::new(&env).apply(pill) {
-                | Ok(pill) => {
+            let (after, requires) = (pill.after.clone(), pill.requires.clone());
+            // errors in a pill of its own file name that file
+            let file = pill.file.clone();
+            let within = |e: anyhow::Error| match &file {
+                | Some(file) => anyhow::anyhow!("in {}: {}", file.display(), e),
+                | None => e,
+            };
+            let tags = (pill.drips.iter().map(|drip| Expr::parse_all(&drip.tags)))
+                .collect::<anyhow::Result<Vec<_>>>()
+                .map_err(|e| within(anyhow::anyhow!("invalid env in pill <{}>: {}", name, e)))?;
+            check_tags(&env_map, Node::Pill(name.clone()), &tags.concat(), lenient)
+                .map_err(within)?;
+            let probing = reach.contains(&name);
+            let drips = (pill.drips.iter().zip(tags.iter()))
+                .map(|(drip, tags)| DripTrace {
+                    tags: tags.clone(),
+                    skipped: if !env.check_all_as(tags, probing) {
+                        Some("env doesn't match".to_owned())
+                    } else {
+                        (drip.cond.as_ref()).and_then(|cond| cond.check_as(machine.assume).err())
+                    },
+                    site: drip.site.clone(),
+                    repo: drip.repo.clone(),
+                    arrows: drip.arrows.clone(),
+                })
+                .collect();
+            let over = machine.overrides.get(&name);
+            let disabled = over.is_some_and(|over| over.disable);
+            traces.insert(name.clone(), PillTrace { drips, disabled });
+            if disabled {
+                log::info!("disabled pill <{}> by override", name);
+                continue;
+            }
+            let applied = DripApplyIncr::new(&env, probing, machine.assume)
+                .apply(pill, tags)
+                .and_then(|mut pill| {
+                    pill.drip.site = ctx.expand(&pill.drip.site)?;
+                    pill.drip.rel_repo = ctx.expand(&pill.drip.rel_repo)?;
+                    for arrow in pill.drip.arrows.iter_mut() {
+                        arrow.rel_site = ctx.expand(&arrow.rel_site)?;
+                    }
+                    let Some(over) = over else {
+                        return Ok(pill);
+                    };
+                    // only pills on this machine are overridden
+                    if !pill.non_empty() {
+                        log::warn!(
+                            "override of <{}> in Underdose.toml is not applied, as no drip of it applies on this machine",
+                            name
+                        );
+                        return Ok(pill);
+                    }
+                    let mut changes = Vec::new();
+                    if let Some(site) = &over.site {
+                        pill.drip.site = ctx.expand(site)?;
+                        changes.push(format!("site = {}", site.display()));
+                    }
+                    for arrow in over.arrows.iter() {
+                        let mut arrow = arrow.clone();
+                        arrow.rel_site = ctx.expand(&arrow.rel_site)?;
+                        changes.push(format!("{}: {}", arrow.rel_site.display(), arrow.src));
+                        match arrow.cond.as_ref().map(Cond::check) {
+                            | Some(Err(reason)) => pill.skipped.push(Skipped {
+                                pill: name.clone(),
+                                arrow: Some(arrow),
+                                reason,
+                            }),
+                            | _ => pill.drip.arrows.push(arrow),
+                        }
+                    }
+                    overridden.insert(name.clone(), changes);
+                    Ok(pill)
+                });
+            match applied {
+                | Ok(mut pill) => {
+                    skipped.append(&mut pill.skipped);
+                    assumed.append(&mut pill.assumed);
                     if pill.non_empty() {
+                        let pill_hooks =
+                            Hooks::new(&pill.name, pill.hooks, &ctx).map_err(within)?;
+                        if !pill_hooks.is_empty() {
+                            hooks.insert(pill.name.to_owned(), pill_hooks);
+                        }
+                        let pill_generators = generators_of(&pill.name, &pill.drip, &machine.local);
+                        if !pill_generators.is_empty() {
+                            generators.insert(pill.name.to_owned(), pill_generators);
+                        }
+                        deps.push((Node::Pill(name.clone()), file.clone(), after, requires));
                         pills.insert(pill.name.to_owned(), pill.drip);
                     } else {
                         log::info!("ignored empty pill <{}>", name)
                     }
                 }
                 | Err(e) => {
+                    let e = within(e);
                     log::warn!("ignored pill <{}>: {}", name, e);
+                    ignored.insert(name, e.to_string());
+                }
+            }
+        }
+
+        let overlaps = overlaps(&pills);
+
+        // resolve names to what is on this machine
+        let resolve = |name: &String| -> Vec<Node> {
+            let mut nodes = Vec::new();
+            if cmds.contains_key(name) {
+                nodes.push(Node::Cmd(name.clone()));
+            }
+            if pills.contains_key(name) {
+                nodes.push(Node::Pill(name.clone()));
+            }
+            nodes
+        };
+        let mut after = HashMap::new();
+        let mut requires = HashMap::new();
+        let mut unmet = IndexMap::new();
+        for (node, file, after_names, requires_names) in deps {
+            for name in after_names.iter().chain(requires_names.iter()) {
+                if !known.contains(name) {
+                    let e = anyhow::anyhow!("{} depends on <{}>, which is not defined", node, name);
+                    match file {
+                        | Some(file) => anyhow::bail!("in {}: {}", file.display(), e),
+                        | None => return Err(e),
+                    }
+                }
+            }
+            let mut required = Vec::new();
+            for name in requires_names.iter() {
+                let nodes = resolve(name);
+                if nodes.is_empty() {
+                    log::warn!("{} requires <{}>, which is not on this machine", node, name);
+                    unmet
+                        .entry(node.clone())
+                        .or_insert_with(Vec::new)
+                        .push(name.clone());
+                }
+                required.extend(nodes);
+            }
+            after.insert(node.clone(), after_names.iter().flat_map(resolve).collect());
+            requires.insert(node, required);
+        }
+        let nodes = (cmds.keys().map(|name| Node::Cmd(name.clone())))
+            .chain(pills.keys().map(|name| Node::Pill(name.clone())))
+            .collect();
+        let schedule = Schedule::new(nodes, after, requires)?;
+
+        let mut groups = IndexMap::new();
+        for (group, members) in store.group {
+            for name in members.pills.iter() {
+                if !known.contains(name) {
+                    anyhow::bail!("group <{}> lists <{}>, which is not defined", group, name)
                 }
             }
+            groups.insert(group, members.pills);
+        }
+
+        Ok(Drugstore {
+            env,
+            detected,
+            probes,
+            cmds,
+            pills,
+            groups,
+            hooks,
+            generators,
+            schedule,
+            skipped,
+            assumed,
+            ignored,
+            traces,
+            files: Vec::new(),
+            overridden,
+            overlaps,
+            unmet,
+        })
+    }
+}
+
+impl Drugstore {
+    /// `run` probes needed so far that could not run as they are not trusted
+    pub fn untrusted_probes(&self) -> Vec<&Command> {
+        (self.env.probed().into_iter())
+            .filter(|(_, _, holds)| holds.is_none())
+            .filter_map(|(tag, _, _)| self.probes.get(&tag))
+            .collect()
+    }
+
+    /// steps named by `names`, each being a name, a glob over names, or a
+    /// `@group`; unordered and without what they require
+    pub fn pick(&self, names: &[String]) -> anyhow::Result<Vec<Node>> {
+        let nodes = |name: &String| {
+            let cmd = (self.cmds.contains_key(name)).then(|| Node::Cmd(name.clone()));
+            let pill = (self.pills.contains_key(name)).then(|| Node::Pill(name.clone()));
+            cmd.into_iter().chain(pill)
+        };
+        let mut picked = Vec::new();
+        for name in names {
+            if let Some(group) = name.strip_prefix('@') {
+                let members = (self.groups.get(group))
+                    .ok_or_else(|| anyhow::anyhow!("no such group: {}", group))?;
+                picked.extend(members.iter().flat_map(nodes));
+                continue;
+            }
+            let before = picked.len();
+            if name.contains(['*', '?', '[']) {
+                let pattern = glob::Pattern::new(name)
+                    .map_err(|e| anyhow::anyhow!("invalid pattern {}: {}", name, e))?;
+                let names = self.cmds.keys().chain(self.pills.keys());
+                picked.extend(names.filter(|n| pattern.matches(n)).flat_map(nodes));
+            } else {
+                picked.extend(nodes(name));
+            }
+            if picked.len() == before {
+                anyhow::bail!("no such pill or command: {}", name);
+            }
         }
+        Ok(picked)
+    }
 
-        Ok(Drugstore { env, cmds, pills })
+    /// steps to run for the given names, or everything if none is given,
+    /// leaving out the skipped ones even if required
+    pub fn select(&self, names: &[String], skip: &[String]) -> anyhow::Result<Vec<Node>> {
+        let mut selected = if names.is_empty() {
+            self.schedule.order.clone()
+        } else {
+            self.schedule.select(self.pick(names)?)
+        };
+        let skip: HashSet<_> = self.pick(skip)?.into_iter().collect();
+        selected.retain(|node| !skip.contains(node));
+        for node in selected.iter() {
+            if let Some(names) = self.unmet.get(node) {
+                anyhow::bail!(
+                    "{} requires <{}>, which is not available on this machine",
+                    node,
+                    names.join(">, <")
+                );
+            }
+        }
+        Ok(selected)
+    }
+
+    /// pills named by `names`, or all if none is given, leaving out the skipped
+    pub fn pick_pills(&self, names: &[String], skip: &[String]) -> anyhow::Result<Vec<&str>> {
+        let picked: HashSet<_> = self.pick(names)?.into_iter().collect();
+        let skip: HashSet<_> = self.pick(skip)?.into_iter().collect();
+        let pills = self.pills.keys().filter(|name| {
+            let node = Node::Pill(name.to_string());
+            (names.is_empty() || picked.contains(&node)) && !skip.contains(&node)
+        });
+        Ok(pills.map(String::as_str).collect())
     }
 }
 
+/// names of the steps picked by `names`, or all if none is given, with what
+/// they require but without those picked by `skip`; the declared steps are
+/// taken, whether or not on this machine
+fn reach(store: &parse::Drugstore, names: &[String], skip: &[String]) -> HashSet<String> {
+    let declared = || {
+        (store.cmd.iter().map(|cmd| (&cmd.name, &cmd.requires)))
+            .chain(store.pill.iter().map(|pill| (&pill.name, &pill.requires)))
+    };
+    // names are checked when picked from the store, so unknown ones pick nothing
+    let matching = |name: &String| -> Vec<String> {
+        if let Some(group) = name.strip_prefix('@') {
+            return (store.group.get(group)).map_or_else(Vec::new, |group| group.pills.clone());
+        }
+        if !name.contains(['*', '?', '[']) {
+            return vec![name.clone()];
+        }
+        let Ok(pattern) = glob::Pattern::new(name) else {
+            return Vec::new();
+        };
+        (declared().map(|(name, _)| name))
+            .filter(|name| pattern.matches(name))
+            .cloned()
+            .collect()
+    };
+    let mut stack: Vec<_> = if names.is_empty() {
+        declared().map(|(name, _)| name.clone()).collect()
+    } else {
+        names.iter().flat_map(matching).collect()
+    };
+    let mut reach = HashSet::new();
+    while let Some(name) = stack.pop() {
+        if reach.insert(name.clone()) {
+            let requires = declared().filter(|(declared, _)| **declared == name);
+            stack.extend(requires.flat_map(|(_, requires)| requires.iter().cloned()));
+        }
+    }
+    for name in skip.iter().flat_map(matching) {
+        reach.remove(&name);
+    }
+    reach
+}
+
+/// the programs of the `src.cmd` arrows of a pill, run in its repo and named
+/// apart like hooks
+fn generators_of(pill: &str, drip: &Drip, root: &Path) -> Vec<Command> {
+    (drip.arrows.iter())
+        .filter_map(|arrow| match &arrow.src {
+            | ArrowSrc::Cmd(generator) => Some(generator),
+            | _ => None,
+        })
+        .enumerate()
+        .map(|(i, Generator { prog, args, .. })| Command {
+            name: format!("{}.cmd.{}", pill, i + 1),
+            prog: prog.clone(),
+            args: args.clone(),
+            cwd: root.join(&drip.rel_repo),
+            vars: IndexMap::new(),
+            timeout: None,
+            guard: Guard::default(),
+        })
+        .collect()
+}
+
+/// pairs of arrows whose sites are the same or nested, so that pouring one
+/// would dump the other into dreams; sites edited in place may be shared
+fn overlaps(pills: &IndexMap<String, Drip>) -> Vec<Overlap> {
+    let sites: Vec<_> = (pills.iter())
+        .flat_map(|(name, drip)| {
+            (drip.arrows.iter()).map(move |arrow| {
+                let site: PathBuf = drip.site.join(&arrow.rel_site).components().collect();
+                let full = crate::utils::path::expand_home(&site);
+                (name, site, full, arrow.src.in_place())
+            })
+        })
+        .collect();
+    let mut res = Vec::new();
+    for (i, (a, a_site, a_full, a_in_place)) in sites.iter().enumerate() {
+        for (b, b_site, b_full, b_in_place) in sites[i + 1..].iter() {
+            if *a_in_place && *b_in_place {
+                continue;
+            }
+            let (owner, verb) = if a == b {
+                (format!("pill <{}>", a), "pours")
+            } else {
+                (format!("pills <{}> and <{}>", a, b), "pour")
+            };
+            let (a_site, b_site) = (a_site.display(), b_site.display());
+            let what = if a_full == b_full && a == b {
+                format!("{} pours {} twice", owner, a_site)
+            } else if a_full == b_full {
+                format!("{} both pour {}", owner, a_site)
+            } else if b_full.starts_with(a_full) {
+                format!("{} {} {} inside {}", owner, verb, b_site, a_site)
+            } else if a_full.starts_with(b_full) {
+                format!("{} {} {} inside {}", owner, verb, a_site, b_site)
+            } else {
+                continue;
+            };
+            res.push(Overlap {
+                pills: [a.to_string(), b.to_string()],
+                what,
+            });
+        }
+    }
+    res
+}
+
+/// an undefined tag never matches, and an ambiguous one matches any of the tags
+/// it may refer to, neither of which is likely what's meant
+fn check_tags(env_map: &EnvMap, node: Node, exprs: &[Expr], lenient: bool) -> anyhow::Result<()> {
+    for tag in exprs.iter().flat_map(Expr::tags) {
+        let paths = env_map.lookup(tag);
+        if paths.is_empty() {
+            let e = format!("{}: {}", node, env_map.undefined(tag));
+            if !lenient {
+                anyhow::bail!("{}", e);
+            }
+            log::warn!("{}", e);
+        }
+        if paths.len() > 1 {
+            log::warn!(
+                "tag {} in {} is ambiguous, qualify it as one of {}",
+                tag,
+                node,
+                paths.join(", ")
+            );
+        }
+    }
+    Ok(())
+}
+
 struct DripApplyIncr<'a> {
     drip: parse::Drip,
+    hooks: parse::Hooks,
+    skipped: Vec<Skipped>,
+    assumed: Vec<Assumed>,
     pub envset: &'a EnvSet,
+    /// whether probes may be run to check the env
+    probing: bool,
+    /// what conditions are taken as, if they can't be checked
+    assume: Option<bool>,
 }
 
 impl<'a> DripApplyIncr<'a> {
-    fn new(envset: &'a EnvSet) -> Self {
+    fn new(envset: &'a EnvSet, probing: bool, assume: Option<bool>) -> Self {
         DripApplyIncr {
             drip: parse::Drip {
                 tags: HashSet::new(),
+                cond: None,
+                hook: parse::Hooks::default(),
                 site: None,
                 repo: None,
                 arrows: Vec::new(),
             },
+            hooks: parse::Hooks::default(),
+            skipped: Vec::new(),
+            assumed: Vec::new(),
             envset,
+            probing,
+            assume,
+        }
+    }
+
+    /// `Err` with why if the condition fails; one that is only assumed to
+    /// hold is recorded
+    fn holds(
+        &mut self, pill: &str, arrow: Option<&Arrow>, cond: Option<&Cond>,
+    ) -> Result<(), String> {
+        let Some(cond) = cond else {
+            return Ok(());
+        };
+        cond.check_as(self.assume)?;
+        if self.assume.is_some() {
+            self.assumed.push(Assumed {
+                pill: pill.to_owned(),
+                arrow: arrow.cloned(),
+                cond: cond.clone(),
+            });
         }
+        Ok(())
     }
-    fn apply_unchecked(&mut self, drip: parse::Drip) -> anyhow::Result<()> {
+    fn apply_unchecked(&mut self, name: &str, drip: parse::Drip) -> anyhow::Result<()> {
         self.drip.site = match (drip.site, self.drip.site.clone()) {
             | (Some(_), Some(_)) => Err(anyhow::anyhow!("site set multiple times"))?,
             | (new @ Some(_), _) => new,
@@ -224,16 +1260,49 @@ impl<'a> DripApplyIncr<'a> {
             | (new @ Some(_), _) => new,
             | (None, old) => old,
         };
-        self.drip.arrows.extend(drip.arrows);
+        self.hooks.extend(drip.hook);
+        for arrow in drip.arrows {
+            if let Err(reason) = self.holds(name, Some(&arrow), arrow.cond.as_ref()) {
+                self.skipped.push(Skipped {
+                    pill: name.to_owned(),
+                    arrow: Some(arrow),
+                    reason,
+                });
+                continue;
+            }
+            // fragments of the same site are concatenated in drip order
+            if let ArrowSrc::Concat(concat) = &arrow.src {
+                let same = self.drip.arrows.iter_mut().find_map(|a| match &mut a.src {
+                    | ArrowSrc::Concat(c) if a.rel_site == arrow.rel_site => Some(c),
+                    | _ => None,
+                });
+                if let Some(same) = same {
+                    same.absorb(concat.clone())?;
+                    continue;
+                }
+            }
+            self.drip.arrows.push(arrow);
+        }
         Ok(())
     }
-    pub fn apply(mut self, pill: parse::Pill) -> anyhow::Result<Pill> {
+    /// `tags` are the parsed tags of each drip
+    pub fn apply(mut self, pill: parse::Pill, tags: Vec<Vec<Expr>>) -> anyhow::Result<Pill> {
+        self.hooks.extend(pill.hook);
         let mut cnt = 0;
-        for drip in pill.drips {
-            if self.envset.check_all(&drip.tags) {
-                self.apply_unchecked(drip)?;
-                cnt += 1;
+        for (drip, tags) in pill.drips.into_iter().zip(tags) {
+            if !self.envset.check_all_as(&tags, self.probing) {
+                continue;
+            }
+            if let Err(reason) = self.holds(&pill.name, None, drip.cond.as_ref()) {
+                self.skipped.push(Skipped {
+                    pill: pill.name.clone(),
+                    arrow: None,
+                    reason,
+                });
+                continue;
             }
+            self.apply_unchecked(&pill.name, drip)?;
+            cnt += 1;
         }
 
         if cnt == 0 {
@@ -241,6 +1310,9 @@ impl<'a> DripApplyIncr<'a> {
             return Ok(Pill {
                 name: pill.name,
                 drip: Drip::default(),
+                hooks: parse::Hooks::default(),
+                skipped: self.skipped,
+                assumed: self.assumed,
             });
         }
 
@@ -251,7 +1323,8 @@ impl<'a> DripApplyIncr<'a> {
                 pill.name
             )
         })?;
-        let rel_repo = self.drip.repo.unwrap_or(PathBuf::from(pill.name.clone()));
+        let rel_repo =
+            (self.drip.repo.or(pill.dir)).unwrap_or_else(|| PathBuf::from(pill.name.clone()));
         let arrows = self.drip.arrows;
         Ok(Pill {
             name: pill.name,
@@ -260,6 +1333,9 @@ impl<'a> DripApplyIncr<'a> {
                 rel_repo,
                 arrows,
             },
+            hooks: self.hooks,
+            skipped: self.skipped,
+            assumed: self.assumed,
         })
     }
 }
@@ -281,10 +1357,521 @@ mod tests {
 
         // parse with mac
         let machine = crate::Machine {
-            env: ["mac".to_owned()].into(),
+            env: ["sys.mac".to_owned()].into(),
             ..Default::default()
         };
         let store = crate::Drugstore::try_from((toml, &machine)).unwrap();
         println!("mac: {:#?}", store);
     }
+
+    #[test]
+    fn concat_fragments() {
+        let content = crate::utils::tests::remove_tutorial(crate::utils::conf::DRUGSTORE_TOML);
+        let toml = crate::drugstore::TomlStr::new(&content[..]);
+
+        let machine = crate::Machine {
+            env: ["linux.arch".to_owned()].into(),
+            ..Default::default()
+        };
+        let store = crate::Drugstore::try_from((toml, &machine)).unwrap();
+        let concats: Vec<_> = store.pills["zsh"]
+            .arrows
+            .iter()
+            .filter_map(|arrow| match &arrow.src {
+                | crate::ArrowSrc::Concat(concat) => Some(concat),
+                | _ => None,
+            })
+            .collect();
+        assert_eq!(concats.len(), 1);
+        assert_eq!(
+            concats[0].parts,
+            ["functions/common.zsh", "functions/arch.zsh"].map(std::path::PathBuf::from)
+        );
+        assert_eq!(concats[0].header.as_deref(), Some("# from {}"));
+    }
+
+    #[test]
+    fn unmet_requires() {
+        let buf = r#"
+            [env.linux]
+            [env.mac]
+            [[cmd]]
+            name = "brew"
+            env = ["mac"]
+            prog = "true"
+            [[pill]]
+            name = "vim"
+            requires = ["brew"]
+            [[pill.drip]]
+            site = "~"
+            [[pill.drip.arrow]]
+            site = ".vimrc"
+            src.link = "vimrc"
+            [[pill]]
+            name = "git"
+            [[pill.drip]]
+            site = "~"
+            [[pill.drip.arrow]]
+            site = ".gitconfig"
+            src.link = "gitconfig"
+        "#;
+        let machine = crate::Machine {
+            env: ["linux".to_owned()].into(),
+            ..Default::default()
+        };
+        // loads, but only what doesn't need brew can be synced
+        let store =
+            crate::Drugstore::try_from((crate::drugstore::TomlStr::new(buf), &machine)).unwrap();
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            store.select(&names(&["git"]), &[]).unwrap(),
+            [crate::schedule::Node::Pill("git".to_owned())]
+        );
+        assert_eq!(
+            store.select(&names(&["vim"]), &[]).unwrap_err().to_string(),
+            "pill <vim> requires <brew>, which is not available on this machine"
+        );
+        assert!(store.select(&[], &names(&["vim"])).is_ok());
+    }
+
+    #[test]
+    fn probes_in_reach() {
+        let buf = r#"
+            [env.work]
+            probe.run = ["true"]
+            [[pill]]
+            name = "vpn"
+            [[pill.drip]]
+            env = ["work"]
+            site = "~"
+            [[pill.drip.arrow]]
+            site = ".vpn"
+            src.link = "vpn"
+            [[pill]]
+            name = "git"
+            [[pill.drip]]
+            site = "~"
+            [[pill.drip.arrow]]
+            site = ".gitconfig"
+            src.link = "gitconfig"
+        "#;
+        // probes run in the drugstore root, which pills are gathered from too
+        let local = std::env::temp_dir().join(format!("underdose-probes-{}", std::process::id()));
+        std::fs::create_dir_all(&local).unwrap();
+        let machine = crate::Machine {
+            local: local.clone(),
+            ..Default::default()
+        };
+        let names = ["git".to_owned(), "vpn".to_owned()];
+        let load = |probing: &crate::drugstore::Probing| {
+            let toml = crate::drugstore::TomlStr::new(buf);
+            crate::Drugstore::load(toml, &machine, false, probing).unwrap()
+        };
+        // git needs no probe, and vpn is out of reach
+        let probing = crate::drugstore::Probing {
+            names: &names[..1],
+            ..Default::default()
+        };
+        let store = load(&probing);
+        assert!(store.env.probed().is_empty() && !store.pills.contains_key("vpn"));
+        // vpn needs the probe, which doesn't run before it is trusted
+        let probing = crate::drugstore::Probing {
+            names: &names[1..],
+            ..Default::default()
+        };
+        let store = load(&probing);
+        let untrusted: Vec<_> = store
+            .untrusted_probes()
+            .iter()
+            .map(|cmd| cmd.name.clone())
+            .collect();
+        assert_eq!(untrusted, ["env.work.probe"]);
+        assert!(!store.pills.contains_key("vpn"));
+        let mut ledger = crate::Ledger::default();
+        ledger.trust(&store.probes["work"]);
+        let probing = crate::drugstore::Probing {
+            names: &names[1..],
+            ledger: Some(&ledger),
+            ..Default::default()
+        };
+        let store = load(&probing);
+        assert!(store.untrusted_probes().is_empty() && store.pills.contains_key("vpn"));
+        std::fs::remove_dir_all(&local).unwrap();
+    }
+
+    #[test]
+    fn command_guards() {
+        let dir = std::env::temp_dir().join(format!("underdose-guards-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let buf = format!(
+            r#"
+            env = {{}}
+            [[cmd]]
+            name = "made"
+            prog = "true"
+            creates = {:?}
+            [[cmd]]
+            name = "installed"
+            prog = "true"
+            unless = ["true"]
+            [[cmd]]
+            name = "missing"
+            prog = "true"
+            unless = ["false"]
+            [[cmd]]
+            name = "setup"
+            prog = "true"
+            once = true
+        "#,
+            dir
+        );
+        let machine = crate::Machine {
+            local: dir.clone(),
+            ..Default::default()
+        };
+        let store =
+            crate::Drugstore::try_from((crate::drugstore::TomlStr::new(&buf), &machine)).unwrap();
+        let skipped = |name: &str, ledger: &crate::Ledger| {
+            store.cmds[name].skipped(ledger, &store.env).unwrap()
+        };
+        let mut ledger = crate::Ledger::default();
+        assert_eq!(
+            skipped("made", &ledger),
+            Some(format!("`{}` exists", dir.display()))
+        );
+        assert_eq!(
+            skipped("installed", &ledger).as_deref(),
+            Some("`true` succeeded")
+        );
+        assert_eq!(skipped("missing", &ledger), None);
+        assert_eq!(skipped("setup", &ledger), None);
+        ledger.once.insert("setup".to_owned());
+        assert_eq!(
+            skipped("setup", &ledger).as_deref(),
+            Some("already run once")
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn command_exec() {
+        let repo = std::env::temp_dir().join(format!("underdose-exec-{}", std::process::id()));
+        std::fs::create_dir_all(repo.join("sub")).unwrap();
+        std::fs::write(repo.join("sub/marker"), "").unwrap();
+        let buf = r#"
+            env = {}
+            [[cmd]]
+            name = "inside"
+            shell = 'test -f marker && test "$FOO" = bar && test "$UD_DRUGSTORE" = "$(cd .. && pwd)"'
+            cwd = "sub"
+            vars = { FOO = "bar" }
+            [[cmd]]
+            name = "outside"
+            prog = "test"
+            args = ["-f", "marker"]
+            [[cmd]]
+            name = "slow"
+            prog = "sleep"
+            args = ["5"]
+            timeout = 1
+        "#;
+        let machine = crate::Machine {
+            local: repo.clone(),
+            ..Default::default()
+        };
+        let store =
+            crate::Drugstore::try_from((crate::drugstore::TomlStr::new(buf), &machine)).unwrap();
+        let inside = store.cmds["inside"].run(&store.env);
+        let outside = store.cmds["outside"].run(&store.env);
+        let start = std::time::Instant::now();
+        let slow = store.cmds["slow"].run(&store.env);
+        let elapsed = start.elapsed();
+        let both = buf.replace("prog = \"test\"", "prog = \"test\"\nshell = \"true\"");
+        let both = crate::Drugstore::try_from((crate::drugstore::TomlStr::new(&both), &machine));
+        std::fs::remove_dir_all(&repo).unwrap();
+
+        assert!(inside.is_ok());
+        assert_eq!(outside.unwrap_err().to_string(), "command failed: outside");
+        assert_eq!(
+            slow.unwrap_err().to_string(),
+            "command <slow> timed out after 1s"
+        );
+        assert!(elapsed < std::time::Duration::from_secs(4));
+        assert_eq!(
+            both.unwrap_err().to_string(),
+            "command <outside> needs exactly one of `prog` and `shell`"
+        );
+    }
+
+    #[test]
+    fn select_pills() {
+        let pill = |name: &str, requires: &str| {
+            format!(
+                "[[pill]]\nname = \"{}\"\nrequires = [{}]\n[[pill.drip]]\nsite = \"~\"\n[[pill.drip.arrow]]\nsite = \".{}\"\nsrc.link = \"{}\"\n",
+                name, requires, name, name
+            )
+        };
+        let buf = format!(
+            "env = {{}}\n[[cmd]]\nname = \"fonts\"\nprog = \"true\"\n{}{}{}[group.edit]\npills = [\"vim\", \"fonts\"]\n",
+            pill("zsh", ""),
+            pill("zsh-plugins", "\"zsh\""),
+            pill("vim", "")
+        );
+        let machine = crate::Machine::default();
+        let store =
+            crate::Drugstore::try_from((crate::drugstore::TomlStr::new(&buf), &machine)).unwrap();
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        let select = |names: &[String], skip: &[String]| {
+            let nodes = store.select(names, skip).unwrap();
+            nodes.iter().map(ToString::to_string).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            select(&names(&["zsh*"]), &[]),
+            ["pill <zsh>", "pill <zsh-plugins>"]
+        );
+        // what is required comes along, unless skipped
+        assert_eq!(
+            select(&names(&["zsh-plugins"]), &[]),
+            ["pill <zsh>", "pill <zsh-plugins>"]
+        );
+        assert_eq!(
+            select(&names(&["zsh-plugins"]), &names(&["zsh"])),
+            ["pill <zsh-plugins>"]
+        );
+        assert_eq!(
+            select(&names(&["@edit"]), &[]),
+            ["command <fonts>", "pill <vim>"]
+        );
+        assert_eq!(select(&[], &names(&["@edit", "zsh-*"])), ["pill <zsh>"]);
+        assert_eq!(store.pick_pills(&names(&["@edit"]), &[]).unwrap(), ["vim"]);
+        assert_eq!(
+            store.pick(&names(&["emacs"])).unwrap_err().to_string(),
+            "no such pill or command: emacs"
+        );
+        assert_eq!(
+            store.pick(&names(&["@work"])).unwrap_err().to_string(),
+            "no such group: work"
+        );
+        assert!(store.pick(&names(&["nvim*"])).is_err());
+    }
+
+    #[test]
+    fn detect_tags() {
+        let buf = r#"
+            [env.sys.linux.arch]
+            [env.sys.linux.debian]
+            [env.headless]
+            [detect]
+            distro = { arch = "arch", debian = "debian" }
+            headless = { true = "headless" }
+        "#;
+        let machine = crate::Machine {
+            facts: crate::env::Facts {
+                map: [("distro", "arch"), ("headless", "false")]
+                    .map(|(fact, value)| (fact, value.to_owned()))
+                    .into_iter()
+                    .collect(),
+            },
+            ..Default::default()
+        };
+        let store =
+            crate::Drugstore::try_from((crate::drugstore::TomlStr::new(buf), &machine)).unwrap();
+        assert_eq!(
+            store.detected.into_iter().collect::<Vec<_>>(),
+            [("sys.linux.arch".to_owned(), "distro = arch".to_owned())]
+        );
+        assert!(store.env.check("linux") && !store.env.check("headless"));
+        let unknown = buf.replace("headless = {", "desktop = {");
+        assert!(
+            crate::Drugstore::try_from((crate::drugstore::TomlStr::new(&unknown), &machine))
+                .unwrap_err()
+                .to_string()
+                .starts_with("unknown fact `desktop` in detect")
+        );
+    }
+
+    #[test]
+    fn overlapping_sites() {
+        let pill = |name: &str, site: &str| {
+            format!(
+                "[[pill]]\nname = \"{}\"\n[[pill.drip]]\nsite = \"~\"\n[[pill.drip.arrow]]\nsite = \"{}\"\nsrc.link = \"{}\"\n",
+                name, site, name
+            )
+        };
+        let buf = format!(
+            "env = {{}}\n{}{}{}",
+            pill("nvim", ".config/nvim"),
+            pill("lazy", ".config/nvim/lazy"),
+            pill("git", ".gitconfig")
+        );
+        let machine = crate::Machine::default();
+        let store =
+            crate::Drugstore::try_from((crate::drugstore::TomlStr::new(&buf), &machine)).unwrap();
+        let [overlap] = &store.overlaps[..] else {
+            panic!("expected one overlap, got {:?}", store.overlaps);
+        };
+        assert_eq!(
+            overlap.to_string(),
+            "pills <nvim> and <lazy> pour ~/.config/nvim/lazy inside ~/.config/nvim"
+        );
+        let pill = |name: &str| crate::schedule::Node::Pill(name.to_owned());
+        assert!(overlap.involves(&[pill("lazy")]));
+        assert!(!overlap.involves(&[pill("git")]));
+    }
+
+    #[test]
+    fn trust_hooks() {
+        let buf = r#"
+            env = {}
+            [[pill]]
+            name = "vim"
+            hook.pre = [{ prog = "true" }, { prog = "false" }]
+            [[pill.drip]]
+            site = "~"
+            [[pill.drip.arrow]]
+            site = ".vimrc"
+            src.link = "vimrc"
+            [[pill.drip.arrow]]
+            site = ".vim/spell.txt"
+            src.cmd = { prog = "sh", args = ["-c", "cat words"] }
+        "#;
+        let machine = crate::Machine::default();
+        let store =
+            crate::Drugstore::try_from((crate::drugstore::TomlStr::new(buf), &machine)).unwrap();
+        let hooks = &store.hooks["vim"].pre;
+        assert_eq!(
+            hooks
+                .iter()
+                .map(|hook| hook.name.as_str())
+                .collect::<Vec<_>>(),
+            ["vim.pre.1", "vim.pre.2"]
+        );
+        // generated sites run programs too
+        let generator = &store.generators["vim"][0];
+        assert_eq!(
+            (generator.name.as_str(), generator.prog.as_str()),
+            ("vim.cmd.1", "sh")
+        );
+        let mut ledger = crate::Ledger::default();
+        hooks.iter().for_each(|hook| ledger.trust(hook));
+        assert!(hooks.iter().all(|hook| ledger.trusts(hook)));
+    }
+
+    #[test]
+    fn override_pills() {
+        let content = crate::utils::tests::remove_tutorial(crate::utils::conf::DRUGSTORE_TOML);
+        let toml = crate::drugstore::TomlStr::new(&content[..]);
+        let overrides = r#"
+            [nvim]
+            disable = true
+            [zsh]
+            site = "/home/zsh"
+            [[zsh.arrow]]
+            site = ".zshrc.local"
+            src.link = "local.zsh"
+        "#;
+        let machine = crate::Machine {
+            env: ["linux.arch".to_owned()].into(),
+            overrides: toml::from_str(overrides).unwrap(),
+            ..Default::default()
+        };
+        let store = crate::Drugstore::try_from((toml, &machine)).unwrap();
+        assert!(!store.pills.contains_key("nvim") && store.traces["nvim"].disabled);
+        assert!(!store.overridden.contains_key("nvim"));
+        let zsh = &store.pills["zsh"];
+        assert_eq!(zsh.site, std::path::PathBuf::from("/home/zsh"));
+        assert_eq!(
+            zsh.arrows.last().unwrap().rel_site,
+            std::path::PathBuf::from(".zshrc.local")
+        );
+        assert_eq!(
+            store.overridden["zsh"],
+            ["site = /home/zsh", ".zshrc.local: ln(local.zsh)"]
+        );
+    }
+
+    #[test]
+    fn gather_files() {
+        let repo = std::env::temp_dir().join(format!("underdose-gather-{}", std::process::id()));
+        std::fs::create_dir_all(repo.join("pills")).unwrap();
+        std::fs::create_dir_all(repo.join("vim")).unwrap();
+        let pill = |name: &str| {
+            format!(
+                "[[pill]]\nname = \"{}\"\n[[pill.drip]]\nsite = \"~\"\n",
+                name
+            )
+        };
+        std::fs::write(repo.join("pills/git.toml"), pill("git")).unwrap();
+        std::fs::write(repo.join("pills/zsh.toml"), pill("zsh")).unwrap();
+        let vim = "name = \"neovim\"\n[[drip]]\nsite = \"~\"\n[[drip.arrow]]\nsite = \".vimrc\"\nsrc.link = \"vimrc\"\n";
+        std::fs::write(repo.join("vim/pill.toml"), vim).unwrap();
+        let machine = crate::Machine {
+            local: repo.clone(),
+            ..Default::default()
+        };
+        let buf = format!("env = {{}}\ninclude = [\"pills/*.toml\"]\n{}", pill("tmux"));
+        let store = crate::Drugstore::try_from((crate::drugstore::TomlStr::new(&buf), &machine));
+        let buf = format!("{}{}", buf, pill("zsh"));
+        let duplicated =
+            crate::Drugstore::try_from((crate::drugstore::TomlStr::new(&buf), &machine));
+        std::fs::write(repo.join("vim/pill.toml"), "[[drip]]\nenv = [\"mac\"]\n").unwrap();
+        let invalid =
+            crate::Drugstore::try_from((crate::drugstore::TomlStr::new("env = {}"), &machine));
+        std::fs::remove_dir_all(&repo).unwrap();
+
+        let store = store.unwrap();
+        assert_eq!(
+            store.files,
+            ["pills/git.toml", "pills/zsh.toml", "vim/pill.toml"].map(std::path::PathBuf::from)
+        );
+        assert_eq!(
+            store.traces.keys().collect::<Vec<_>>(),
+            ["tmux", "git", "zsh", "neovim"]
+        );
+        // a `pill.toml` pours from its directory, whatever the pill is named
+        assert_eq!(
+            store.pills["neovim"].rel_repo,
+            std::path::PathBuf::from("vim")
+        );
+        assert!(
+            invalid
+                .unwrap_err()
+                .to_string()
+                .starts_with("in vim/pill.toml: ")
+        );
+        assert_eq!(
+            duplicated.unwrap_err().to_string(),
+            "pill <zsh> is defined in both Drugstore.toml and pills/zsh.toml"
+        );
+    }
+
+    #[test]
+    fn interpolate_sites() {
+        let content = crate::utils::tests::remove_tutorial(crate::utils::conf::DRUGSTORE_TOML);
+        let toml = crate::drugstore::TomlStr::new(&content[..]);
+
+        let machine = crate::Machine {
+            env: ["linux".to_owned()].into(),
+            ..Default::default()
+        };
+        let store = crate::Drugstore::try_from((toml, &machine)).unwrap();
+        let config = directories::BaseDirs::new()
+            .unwrap()
+            .config_dir()
+            .to_owned();
+        assert_eq!(store.pills["vscode"].site, config.join("Code/User"));
+        // another machine keeps its directories elsewhere
+        let other = machine.synthetic("mac".to_owned(), ["sys.mac".to_owned()].into());
+        let toml = crate::drugstore::TomlStr::new(&content[..]);
+        let store = crate::Drugstore::try_from((toml, &other)).unwrap();
+        assert_eq!(
+            store.pills["vscode"].site,
+            std::path::PathBuf::from("${config}/Code/User")
+        );
+
+        let content = content.replace("site = \"${vscode}\"", "site = \"${nowhere}\"");
+        let toml = crate::drugstore::TomlStr::new(&content[..]);
+        let store = crate::Drugstore::try_from((toml, &machine)).unwrap();
+        assert!(store.ignored["vscode"].contains("nowhere"));
+    }
 }
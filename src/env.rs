@@ -0,0 +1,682 @@
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+/// the `[env]` tree of the drugstore; a tag is referred to by the dotted path
+/// to it, or by any trailing part of the path, like `linux.arch` or `arch` for
+/// `sys.linux.arch`, as long as that is unambiguous
+#[derive(Debug, Default)]
+pub struct EnvMap {
+    pub root: EnvNode,
+}
+
+#[derive(Debug, Default)]
+pub struct EnvNode {
+    pub children: IndexMap<String, EnvNode>,
+    /// the machine gets the tag when the probe holds
+    pub probe: Option<Probe>,
+}
+
+impl EnvNode {
+    /// read a node, where `probe` is a reserved key
+    fn new(path: &str, toml: &toml::Value) -> anyhow::Result<Self> {
+        let mut node = EnvNode::default();
+        if let Some(s) = toml.as_str() {
+            node.insert(path, s, EnvNode::default())?;
+        } else if let Some(m) = toml.as_table() {
+            for (k, v) in m {
+                if k == "probe" {
+                    if path.is_empty() {
+                        anyhow::bail!("probe must be declared under a tag in env");
+                    }
+                    let probe = Probe::deserialize(v.clone())
+                        .map_err(|e| anyhow::anyhow!("invalid probe of tag {}: {}", path, e))?;
+                    if probe.exists.is_none() && probe.run.as_ref().is_none_or(Vec::is_empty) {
+                        anyhow::bail!("probe of tag {} has neither `exists` nor `run`", path);
+                    }
+                    node.probe = Some(probe);
+                    continue;
+                }
+                let child = EnvNode::new(&join(path, k), v)?;
+                node.insert(path, k, child)?;
+            }
+        }
+        Ok(node)
+    }
+    fn insert(&mut self, path: &str, name: &str, child: EnvNode) -> anyhow::Result<()> {
+        if name.is_empty() || name.contains('.') {
+            anyhow::bail!("invalid tag name `{}` in env {}", name, path);
+        }
+        self.children.insert(name.to_owned(), child);
+        Ok(())
+    }
+}
+
+/// `path.name`, or `name` at the root
+fn join(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{}.{}", path, name)
+    }
+}
+
+/// whether the tag path is referred to by `tag`, a trailing part of it
+fn refers(path: &str, tag: &str) -> bool {
+    let (mut path, mut tag) = (path.rsplit('.'), tag.rsplit('.'));
+    loop {
+        match (tag.next(), path.next()) {
+            | (None, _) => return true,
+            | (Some(t), Some(p)) if t == p => continue,
+            | _ => return false,
+        }
+    }
+}
+
+impl EnvMap {
+    pub fn new(toml: &toml::Value) -> anyhow::Result<Self> {
+        Ok(EnvMap {
+            root: EnvNode::new("", toml)?,
+        })
+    }
+
+    /// full paths of all tags, parents before children
+    pub fn paths(&self) -> Vec<String> {
+        fn walk(node: &EnvNode, path: &str, res: &mut Vec<String>) {
+            for (name, child) in node.children.iter() {
+                let path = join(path, name);
+                res.push(path.clone());
+                walk(child, &path, res);
+            }
+        }
+        let mut res = Vec::new();
+        walk(&self.root, "", &mut res);
+        res
+    }
+
+    /// full paths of the tags without children
+    pub fn leaves(&self) -> Vec<String> {
+        fn walk(node: &EnvNode, path: &str, res: &mut Vec<String>) {
+            for (name, child) in node.children.iter() {
+                let path = join(path, name);
+                if child.children.is_empty() {
+                    res.push(path);
+                } else {
+                    walk(child, &path, res);
+                }
+            }
+        }
+        let mut res = Vec::new();
+        walk(&self.root, "", &mut res);
+        res
+    }
+
+    /// full paths of the tags that `tag` may refer to
+    pub fn lookup(&self, tag: &str) -> Vec<String> {
+        self.paths()
+            .into_iter()
+            .filter(|path| refers(path, tag))
+            .collect()
+    }
+
+    /// the full path of the only tag that `tag` refers to
+    pub fn find(&self, tag: &str) -> anyhow::Result<String> {
+        match self.lookup(tag).as_slice() {
+            | [] => anyhow::bail!("{}", self.undefined(tag)),
+            | [path] => Ok(path.clone()),
+            | paths => anyhow::bail!(
+                "tag {} is ambiguous, qualify it as one of {}",
+                tag,
+                paths.join(", ")
+            ),
+        }
+    }
+
+    /// complaint about an undefined tag, suggesting the closest defined one
+    pub fn undefined(&self, tag: &str) -> String {
+        let candidates = self.paths().into_iter().flat_map(|path| {
+            let segments: Vec<_> = path.split('.').map(str::to_owned).collect();
+            (0..segments.len()).map(move |i| segments[i..].join("."))
+        });
+        let closest = candidates
+            .map(|candidate| (distance(tag, &candidate), candidate))
+            .filter(|(d, _)| *d <= (tag.chars().count() / 3).max(1))
+            .min();
+        match closest {
+            | Some((_, candidate)) => {
+                format!(
+                    "tag {} is not defined in env; did you mean {}?",
+                    tag, candidate
+                )
+            }
+            | None => format!("tag {} is not defined in env", tag),
+        }
+    }
+
+    /// full paths of the tags carrying a probe, with the probes
+    pub fn probes(&self) -> Vec<(String, &Probe)> {
+        fn walk<'e>(node: &'e EnvNode, path: &str, res: &mut Vec<(String, &'e Probe)>) {
+            for (name, child) in node.children.iter() {
+                let path = join(path, name);
+                if let Some(probe) = &child.probe {
+                    res.push((path.clone(), probe));
+                }
+                walk(child, &path, res);
+            }
+        }
+        let mut res = Vec::new();
+        walk(&self.root, "", &mut res);
+        res
+    }
+
+    /// the machine's tags along with all their parents, by full path
+    pub fn resolve<'a>(
+        &self, tags: impl IntoIterator<Item = &'a String>,
+    ) -> anyhow::Result<EnvSet> {
+        let mut res = HashSet::new();
+        for tag in tags {
+            let path = self.find(tag)?;
+            let segments: Vec<_> = path.split('.').collect();
+            for i in 1..=segments.len() {
+                res.insert(segments[..i].join("."));
+            }
+        }
+        Ok(EnvSet {
+            set: res,
+            ..Default::default()
+        })
+    }
+}
+
+/// edit distance between two strings
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let next = (row[j + 1] + 1)
+                .min(row[j] + 1)
+                .min(prev + usize::from(ca != *cb));
+            prev = row[j + 1];
+            row[j + 1] = next;
+        }
+    }
+    row[b.len()]
+}
+
+/// a set of machine possessed envs, by full path
+#[derive(Debug, Default)]
+pub struct EnvSet {
+    /// tags known without probing, along with their parents
+    pub set: HashSet<String>,
+    /// probes by the full path of their tag, each run once a check needs it
+    probes: IndexMap<String, Probe>,
+    /// tags whose probes may run; others are taken not to hold
+    allowed: HashSet<String>,
+    /// where probes run
+    root: PathBuf,
+    /// outcome of each probe needed so far, `None` if it was not allowed to run
+    probed: RefCell<IndexMap<String, Option<bool>>>,
+}
+
+impl EnvSet {
+    /// add probes to be run as needed, those of `allowed` tags only
+    pub fn with_probes(
+        mut self, probes: IndexMap<String, Probe>, allowed: HashSet<String>, root: &Path,
+    ) -> Self {
+        self.probes = probes;
+        self.allowed = allowed;
+        self.root = root.to_owned();
+        self
+    }
+
+    /// whether the machine has any tag that `tag` refers to, running the
+    /// probes that decide it if `probing`
+    fn check_as(&self, tag: &str, probing: bool) -> bool {
+        if self.set.iter().any(|path| refers(path, tag)) {
+            return true;
+        }
+        // a probe that holds gives its tag along with the parents
+        let decides = |path: &str| {
+            let segments: Vec<_> = path.split('.').collect();
+            (1..=segments.len()).any(|i| refers(&segments[..i].join("."), tag))
+        };
+        let probes = self.probes.keys().filter(|path| decides(path));
+        probes
+            .collect::<Vec<_>>()
+            .into_iter()
+            .any(|path| match probing {
+                | true => self.probe(path) == Some(true),
+                | false => self.probed.borrow().get(path) == Some(&Some(true)),
+            })
+    }
+
+    /// run the probe of the tag at `path`, once
+    fn probe(&self, path: &str) -> Option<bool> {
+        if let Some(holds) = self.probed.borrow().get(path) {
+            return *holds;
+        }
+        let holds = (self.allowed.contains(path)).then(|| self.probes[path].holds(&self.root));
+        self.probed.borrow_mut().insert(path.to_owned(), holds);
+        holds
+    }
+
+    /// whether the machine has any tag that `tag` refers to
+    pub fn check(&self, tag: &str) -> bool {
+        self.check_as(tag, true)
+    }
+    pub fn check_all(&self, exprs: &[Expr]) -> bool {
+        self.check_all_as(exprs, true)
+    }
+    /// as `check_all`, but without running probes if not `probing`
+    pub fn check_all_as(&self, exprs: &[Expr], probing: bool) -> bool {
+        (exprs.iter()).all(|expr| expr.eval_with(&|tag| self.check_as(tag, probing)))
+    }
+
+    /// run every probe allowed to
+    pub fn probe_all(&self) {
+        for path in self.probes.keys() {
+            self.probe(path);
+        }
+    }
+
+    /// probes run so far with their outcomes, `None` if not allowed to run
+    pub fn probed(&self) -> Vec<(String, &Probe, Option<bool>)> {
+        let probed = self.probed.borrow();
+        (probed.iter())
+            .map(|(path, holds)| (path.clone(), &self.probes[path], *holds))
+            .collect()
+    }
+
+    /// tags of the machine by full path, sorted, with every probe allowed to
+    /// run having run
+    pub fn all_tags(&self) -> Vec<String> {
+        self.probe_all();
+        self.tags()
+    }
+
+    /// tags of the machine as known so far, by full path, sorted
+    pub fn tags(&self) -> Vec<String> {
+        let mut tags = self.set.clone();
+        for (path, holds) in self.probed.borrow().iter() {
+            if *holds == Some(true) {
+                let segments: Vec<_> = path.split('.').collect();
+                tags.extend((1..=segments.len()).map(|i| segments[..i].join(".")));
+            }
+        }
+        let mut tags: Vec<_> = tags.into_iter().collect();
+        tags.sort();
+        tags
+    }
+}
+
+/// a check on the machine deciding whether it has a tag
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Probe {
+    /// a path that must exist
+    pub exists: Option<PathBuf>,
+    /// a program that must exit successfully
+    pub run: Option<Vec<String>>,
+}
+
+impl Probe {
+    /// `run` is run in `root`, the drugstore root
+    pub fn holds(&self, root: &Path) -> bool {
+        if let Some(exists) = &self.exists
+            && !crate::utils::path::expand_home(exists).exists()
+        {
+            return false;
+        }
+        if let Some([prog, args @ ..]) = self.run.as_deref() {
+            let status = std::process::Command::new(prog)
+                .args(args)
+                .current_dir(root)
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null())
+                .status();
+            if !status.is_ok_and(|status| status.success()) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl Display for Probe {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut checks = Vec::new();
+        if let Some(exists) = &self.exists {
+            checks.push(format!("`{}` exists", exists.display()));
+        }
+        if let Some(run) = &self.run {
+            checks.push(format!("`{}` succeeds", run.join(" ")));
+        }
+        write!(f, "{}", checks.join(" and "))
+    }
+}
+
+/// facts about the running system, detected on every run
+#[derive(Debug, Clone, Default)]
+pub struct Facts {
+    pub map: IndexMap<&'static str, String>,
+}
+
+impl Facts {
+    /// names of all facts that may be detected
+    pub const NAMES: [&'static str; 7] = [
+        "os", "family", "arch", "distro", "hostname", "wsl", "headless",
+    ];
+
+    pub fn detect() -> Self {
+        let mut map = IndexMap::new();
+        map.insert("os", std::env::consts::OS.to_owned());
+        map.insert("family", std::env::consts::FAMILY.to_owned());
+        map.insert("arch", std::env::consts::ARCH.to_owned());
+        if let Some(distro) = Self::distro() {
+            map.insert("distro", distro);
+        }
+        if let Some(hostname) = Self::hostname() {
+            map.insert("hostname", hostname);
+        }
+        let wsl = std::env::var_os("WSL_DISTRO_NAME").is_some()
+            || std::fs::read_to_string("/proc/version")
+                .is_ok_and(|version| version.to_lowercase().contains("microsoft"));
+        map.insert("wsl", wsl.to_string());
+        let headless = cfg!(all(unix, not(target_os = "macos")))
+            && std::env::var_os("DISPLAY").is_none()
+            && std::env::var_os("WAYLAND_DISPLAY").is_none();
+        map.insert("headless", headless.to_string());
+        Facts { map }
+    }
+
+    /// `ID` in `/etc/os-release`
+    fn distro() -> Option<String> {
+        let release = std::fs::read_to_string("/etc/os-release").ok()?;
+        let id = release.lines().find_map(|line| line.strip_prefix("ID="))?;
+        Some(id.trim().trim_matches('"').to_owned())
+    }
+
+    fn hostname() -> Option<String> {
+        let hostname = match std::fs::read_to_string("/proc/sys/kernel/hostname") {
+            | Ok(hostname) => hostname,
+            | Err(_) => {
+                let output = std::process::Command::new("hostname").output().ok()?;
+                String::from_utf8(output.stdout).ok()?
+            }
+        };
+        Some(hostname.trim().to_owned()).filter(|hostname| !hostname.is_empty())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.map.get(name).map(String::as_str)
+    }
+}
+
+/// a condition over env tags, like `linux|bsd` or `!(mac&school)`; `!` binds
+/// tighter than `&`, which binds tighter than `|`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Tag(String),
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+}
+
+impl Expr {
+    pub fn eval(&self, env: &EnvSet) -> bool {
+        self.eval_with(&|tag| env.check(tag))
+    }
+
+    /// evaluate with `check` telling whether the machine has a tag
+    pub fn eval_with(&self, check: &dyn Fn(&str) -> bool) -> bool {
+        match self {
+            | Expr::Tag(tag) => check(tag),
+            | Expr::Not(expr) => !expr.eval_with(check),
+            | Expr::And(exprs) => exprs.iter().all(|expr| expr.eval_with(check)),
+            | Expr::Or(exprs) => exprs.iter().any(|expr| expr.eval_with(check)),
+        }
+    }
+
+    /// every tag mentioned
+    pub fn tags(&self) -> Vec<&str> {
+        match self {
+            | Expr::Tag(tag) => vec![tag],
+            | Expr::Not(expr) => expr.tags(),
+            | Expr::And(exprs) | Expr::Or(exprs) => exprs.iter().flat_map(Expr::tags).collect(),
+        }
+    }
+
+    pub fn parse(s: &str) -> anyhow::Result<Self> {
+        let mut parser = Parser { s, pos: 0 };
+        let expr = parser.or()?;
+        parser.skip_ws();
+        if parser.pos < s.len() {
+            anyhow::bail!("unexpected `{}` in `{}`", &s[parser.pos..], s);
+        }
+        Ok(expr)
+    }
+
+    /// all tags of a drip or command, each being an expression
+    pub fn parse_all<'a>(tags: impl IntoIterator<Item = &'a String>) -> anyhow::Result<Vec<Self>> {
+        tags.into_iter().map(|tag| Self::parse(tag)).collect()
+    }
+}
+
+struct Parser<'s> {
+    s: &'s str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn skip_ws(&mut self) {
+        let rest = &self.s[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_ws();
+        if self.s[self.pos..].starts_with(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+    fn or(&mut self) -> anyhow::Result<Expr> {
+        let mut exprs = vec![self.and()?];
+        while self.eat('|') {
+            exprs.push(self.and()?);
+        }
+        Ok(if exprs.len() == 1 {
+            exprs.remove(0)
+        } else {
+            Expr::Or(exprs)
+        })
+    }
+    fn and(&mut self) -> anyhow::Result<Expr> {
+        let mut exprs = vec![self.not()?];
+        while self.eat('&') {
+            exprs.push(self.not()?);
+        }
+        Ok(if exprs.len() == 1 {
+            exprs.remove(0)
+        } else {
+            Expr::And(exprs)
+        })
+    }
+    fn not(&mut self) -> anyhow::Result<Expr> {
+        if self.eat('!') {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        if self.eat('(') {
+            let expr = self.or()?;
+            if !self.eat(')') {
+                anyhow::bail!("missing `)` in `{}`", self.s);
+            }
+            return Ok(expr);
+        }
+        self.skip_ws();
+        let rest = &self.s[self.pos..];
+        let len = rest
+            .find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '-' | '.')))
+            .unwrap_or(rest.len());
+        if len == 0 {
+            match rest.chars().next() {
+                | Some(c) => anyhow::bail!("expected a tag at `{}` in `{}`", c, self.s),
+                | None => anyhow::bail!("expected a tag at the end of `{}`", self.s),
+            }
+        }
+        self.pos += len;
+        Ok(Expr::Tag(rest[..len].to_owned()))
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let join = |f: &mut std::fmt::Formatter<'_>, exprs: &[Expr], sep: &str| {
+            for (i, expr) in exprs.iter().enumerate() {
+                if i > 0 {
+                    write!(f, "{}", sep)?;
+                }
+                match expr {
+                    | Expr::And(_) | Expr::Or(_) => write!(f, "({})", expr)?,
+                    | _ => write!(f, "{}", expr)?,
+                }
+            }
+            Ok(())
+        };
+        match self {
+            | Expr::Tag(tag) => write!(f, "{}", tag),
+            | Expr::Not(expr) => match expr.as_ref() {
+                | Expr::And(_) | Expr::Or(_) => write!(f, "!({})", expr),
+                | _ => write!(f, "!{}", expr),
+            },
+            | Expr::And(exprs) => join(f, exprs, "&"),
+            | Expr::Or(exprs) => join(f, exprs, "|"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_expr() {
+        let env = EnvSet {
+            set: ["sys", "linux", "arch"].map(str::to_owned).into(),
+            ..Default::default()
+        };
+        let holds = |s: &str| Expr::parse(s).unwrap().eval(&env);
+        assert!(holds("linux"));
+        assert!(holds("!mac"));
+        assert!(holds("mac | arch"));
+        assert!(!holds("linux & !arch"));
+        assert!(holds("!(mac|nixos) & (sys)"));
+        assert_eq!(
+            Expr::parse("a|b&!c").unwrap(),
+            Expr::Or(vec![
+                Expr::Tag("a".to_owned()),
+                Expr::And(vec![
+                    Expr::Tag("b".to_owned()),
+                    Expr::Not(Box::new(Expr::Tag("c".to_owned()))),
+                ]),
+            ])
+        );
+        assert_eq!(Expr::parse("!(a|b)&c").unwrap().to_string(), "!(a|b)&c");
+        for bad in ["", "a|", "(a", "a b", "!&a"] {
+            assert!(Expr::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn env_tree() {
+        let toml: toml::Value =
+            toml::from_str("[sys.mac]\n[sys.linux.arch]\n[school.mac]\n[home]\nlinux = \"arch\"\n")
+                .unwrap();
+        let env = EnvMap::new(&toml).unwrap();
+        assert!(env.find("nixos").is_err());
+        assert_eq!(env.find("sys.mac").unwrap(), "sys.mac");
+        assert!(env.find("mac").is_err());
+        assert_eq!(
+            env.lookup("linux.arch"),
+            ["home.linux.arch", "sys.linux.arch"]
+        );
+        assert_eq!(env.lookup("inux.arch"), Vec::<String>::new());
+        assert!(env.undefined("linx").ends_with("did you mean linux?"));
+        assert!(env.undefined("sys.mak").ends_with("did you mean sys.mac?"));
+        assert!(!env.undefined("windows").contains("did you mean"));
+
+        let set = env.resolve(&["school.mac".to_owned()]).unwrap();
+        assert!(set.check("mac") && set.check("school") && !set.check("sys"));
+        assert!(!set.check("sys.mac"));
+    }
+
+    #[test]
+    fn detect_facts() {
+        let facts = Facts::detect();
+        assert!(facts.map.keys().all(|name| Facts::NAMES.contains(name)));
+        assert_eq!(facts.get("os"), Some(std::env::consts::OS));
+        assert_eq!(facts.get("family"), Some(std::env::consts::FAMILY));
+        assert_eq!(facts.get("arch"), Some(std::env::consts::ARCH));
+        for flag in ["wsl", "headless"] {
+            assert!(
+                matches!(facts.get(flag), Some("true" | "false")),
+                "{}",
+                flag
+            );
+        }
+        if cfg!(target_os = "macos") {
+            assert_eq!(facts.get("headless"), Some("false"));
+        }
+        for name in ["distro", "hostname"] {
+            assert_ne!(facts.get(name), Some(""), "{}", name);
+        }
+    }
+
+    #[test]
+    fn lazy_probes() {
+        let toml: toml::Value = toml::from_str("[work]\nprobe = {}\n").unwrap();
+        assert!(EnvMap::new(&toml).is_err());
+        let toml: toml::Value = toml::from_str("[work]\nprobe.run = []\n").unwrap();
+        assert!(EnvMap::new(&toml).is_err());
+
+        let dir = std::env::temp_dir();
+        let toml = format!(
+            "[work.vpn]\nprobe.exists = {:?}\n[lab]\nprobe.exists = {:?}\n[home]\nprobe.run = [\"true\"]\n",
+            dir, dir
+        );
+        let env = EnvMap::new(&toml::from_str(&toml).unwrap()).unwrap();
+        let probes: IndexMap<_, _> = (env.probes().into_iter())
+            .map(|(tag, probe)| (tag, probe.clone()))
+            .collect();
+        let allowed = ["work.vpn", "lab"].map(str::to_owned).into();
+        let set = (env.resolve([]).unwrap()).with_probes(probes, allowed, &dir);
+        // nothing is probed until needed
+        assert!(!set.check_all_as(&[Expr::parse("work").unwrap()], false));
+        assert!(set.probed().is_empty());
+        assert!(set.check("work") && set.check("vpn"));
+        assert_eq!(set.tags(), ["work", "work.vpn"]);
+        // a `run` probe not allowed is not run, and its tag doesn't hold
+        assert!(!set.check("home"));
+        set.probe_all();
+        let probed: Vec<_> = (set.probed().into_iter())
+            .map(|(tag, _, holds)| (tag, holds))
+            .collect();
+        assert_eq!(
+            probed,
+            [
+                ("work.vpn".to_owned(), Some(true)),
+                ("home".to_owned(), None),
+                ("lab".to_owned(), Some(true)),
+            ]
+        );
+    }
+}
@@ -1,12 +1,16 @@
-use crate::{ArrowSrc, Drip};
+use crate::{ArrowSrc, Block, Drip, Generator, Stamps};
 // use git2::build::RepoBuilder;
 use std::path::Path;
 
 pub struct Executor<'a> {
+    /// name of the pill
+    pub name: &'a str,
     /// where the root of repo is, globally
     pub repo: &'a Path,
     /// the task to complete
     pub drip: &'a Drip,
+    /// records what generated sites are poured from
+    pub stamps: &'a mut Stamps,
 }
 
 impl<'a> Executor<'a> {
@@ -56,8 +60,103 @@ impl<'a> Executor<'a> {
                 | ArrowSrc::Collector => {
                     log::info!("collector {}", site.display());
                 }
+                | ArrowSrc::Archive(rel) => {
+                    let archive = self.repo.join(&self.drip.rel_repo).join(rel);
+
+                    log::info!("extract {} {}", archive.display(), site.display());
+                    if !archive.exists() {
+                        anyhow::bail!("`repo` <{}> does not exist", archive.display())
+                    }
+                    let stamp = (self.stamps.expected(self.repo, self.drip, arrow)?)
+                        .expect("archive arrows are stamped");
+                    let site = crate::utils::path::canonicalize(site)?;
+                    if site.exists() {
+                        anyhow::bail!("`site` <{}> already exists", site.display())
+                    }
+                    std::fs::create_dir_all(&site)?;
+                    extract(&archive, &site).map_err(|e| {
+                        anyhow::anyhow!("extract <{}> failed: {}", archive.display(), e)
+                    })?;
+                    self.stamps.stamp(&site, stamp);
+                }
+                | ArrowSrc::Cmd(Generator { prog, args, .. }) => {
+                    let cwd = self.repo.join(&self.drip.rel_repo);
+
+                    log::info!("{} {} > {}", prog, args.join(" "), site.display());
+                    let stamp = (self.stamps.expected(self.repo, self.drip, arrow)?)
+                        .expect("cmd arrows are stamped");
+                    crate::utils::path::create_dir_parent(&site)?;
+                    let site = crate::utils::path::canonicalize(site)?;
+                    if site.exists() {
+                        anyhow::bail!("`site` <{}> already exists", site.display())
+                    }
+                    let output = std::process::Command::new(prog)
+                        .args(args)
+                        .current_dir(&cwd)
+                        .stderr(std::process::Stdio::inherit())
+                        .output()
+                        .map_err(|e| anyhow::anyhow!("failed to run {}: {}", prog, e))?;
+                    if !output.status.success() {
+                        anyhow::bail!("command failed: {} {}", prog, args.join(" "))
+                    }
+                    std::fs::write(&site, output.stdout)?;
+                    self.stamps.stamp(&site, stamp);
+                }
+                | ArrowSrc::Concat(concat) => {
+                    let root = self.repo.join(&self.drip.rel_repo);
+
+                    log::info!("cat {} > {}", arrow.src, site.display());
+                    let content = concat.assemble(&root)?;
+                    let stamp = crate::stamps::digest([content.as_slice()]);
+                    crate::utils::path::create_dir_parent(&site)?;
+                    let site = crate::utils::path::canonicalize(site)?;
+                    if site.exists() {
+                        anyhow::bail!("`site` <{}> already exists", site.display())
+                    }
+                    std::fs::write(&site, content)?;
+                    self.stamps.stamp(&site, stamp);
+                }
+                | ArrowSrc::Block(block @ Block { file, .. }) => {
+                    let file = self.repo.join(&self.drip.rel_repo).join(file);
+
+                    log::info!("block {} >> {}", file.display(), site.display());
+                    let content = std::fs::read_to_string(&file)
+                        .map_err(|e| anyhow::anyhow!("failed to read {}: {}", file.display(), e))?;
+                    crate::utils::path::create_dir_parent(&site)?;
+                    let site = crate::utils::path::canonicalize(site)?;
+                    let existing = match std::fs::read_to_string(&site) {
+                        | Ok(existing) => existing,
+                        | Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+                        | Err(e) => anyhow::bail!("failed to read {}: {}", site.display(), e),
+                    };
+                    let rendered = block.render(self.name, &content);
+                    std::fs::write(&site, block.splice(self.name, &existing, &rendered))?;
+                }
+                | ArrowSrc::Merge(rel) => {
+                    let fragment = self.repo.join(&self.drip.rel_repo).join(rel);
+
+                    log::info!("merge {} >> {}", fragment.display(), site.display());
+                    crate::utils::path::create_dir_parent(&site)?;
+                    let site = crate::utils::path::canonicalize(site)?;
+                    crate::utils::merge::pour(&fragment, &site)?;
+                }
             }
         }
         Ok(())
     }
 }
+
+fn extract(archive: &Path, site: &Path) -> anyhow::Result<()> {
+    let file = std::fs::File::open(archive)?;
+    let name = archive.to_string_lossy();
+    if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        tar::Archive::new(flate2::read::GzDecoder::new(file)).unpack(site)?;
+    } else if name.ends_with(".tar") {
+        tar::Archive::new(file).unpack(site)?;
+    } else if name.ends_with(".zip") {
+        zip::ZipArchive::new(file)?.extract(site)?;
+    } else {
+        anyhow::bail!("unknown archive format")
+    }
+    Ok(())
+}
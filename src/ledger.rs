@@ -0,0 +1,53 @@
+use crate::{drugstore::Command, utils::global::UNDERDOSE_PATH};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+/// what has been done on this machine, kept across runs; written back only by
+/// `write_index`
+#[derive(Default, Serialize, Deserialize, Debug)]
+pub struct Ledger {
+    /// commands with `once = true` that have succeeded
+    #[serde(default)]
+    pub once: HashSet<String>,
+    /// fingerprints of the commands and hooks approved to run
+    #[serde(default)]
+    pub trusted: HashMap<String, String>,
+}
+
+impl Ledger {
+    fn index_path() -> &'static Path {
+        UNDERDOSE_PATH.ledger.as_path()
+    }
+
+    pub fn new() -> Self {
+        let Ok(content) = std::fs::read_to_string(Self::index_path()) else {
+            return Self::default();
+        };
+        let Ok(res) = serde_json::from_str(&content) else {
+            return Self::default();
+        };
+        res
+    }
+
+    /// whether the command has been approved as it is now
+    pub fn trusts(&self, cmd: &Command) -> bool {
+        self.trusted.get(&cmd.name) == Some(&cmd.fingerprint())
+    }
+
+    pub fn trust(&mut self, cmd: &Command) {
+        self.trusted.insert(cmd.name.clone(), cmd.fingerprint());
+    }
+
+    pub fn write_index(&self) -> anyhow::Result<()> {
+        let content = serde_json::to_string(&self)
+            .map_err(|e| anyhow::anyhow!("failed to serialize ledger: {}", e))?;
+        crate::utils::path::create_dir_parent(Self::index_path())?;
+        std::fs::write(Self::index_path(), content)
+            .map_err(|e| anyhow::anyhow!("failed to write ledger: {}", e))?;
+        log::trace!("dumped ledger at {}", Self::index_path().display());
+        Ok(())
+    }
+}
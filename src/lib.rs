@@ -5,23 +5,32 @@ pub mod cli {
 pub use cli::interface::Cli;
 
 mod machine;
+mod env;
 mod drugstore;
+mod schedule;
+mod check;
+pub use check::Report;
 pub use drugstore::Drugstore;
 pub use machine::Machine;
 
 mod executor;
 mod observor;
 mod dreamer;
+mod stamps;
+mod ledger;
 pub use dreamer::Dreamer;
 pub use executor::Executor;
+pub use ledger::Ledger;
+pub use stamps::Stamps;
 
 mod drip;
-pub use drip::{Arrow, ArrowSrc, Drip};
+pub use drip::{Arrow, ArrowSrc, Block, Concat, Cond, Drip, Generator};
 
 pub mod utils {
     pub mod conf;
     pub mod repo;
     pub mod path;
+    pub mod merge;
 
     pub mod global;
     #[cfg(test)]
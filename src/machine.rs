@@ -1,12 +1,53 @@
-use crate::utils::path;
+use crate::{Arrow, env::Facts, utils::path};
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 use std::{collections::HashSet, path::PathBuf};
 
 #[derive(Default, Debug, Clone)]
 pub struct Machine {
     pub name: String,
+    /// tags given in `Underdose.toml`
     pub env: HashSet<String>,
+    /// detected from the running system, to be mapped onto tags
+    pub facts: Facts,
     pub local: PathBuf,
+    /// made up to evaluate the drugstore as, so that nothing is detected or
+    /// probed on the running system
+    pub synthetic: bool,
+    /// changes to pills on this machine only, by pill name
+    pub overrides: IndexMap<String, Override>,
+    /// `if` conditions of drips and arrows are taken as this instead of
+    /// checked, as they can only be checked on the running system
+    pub assume: Option<bool>,
+}
+
+/// a change to a pill made in `Underdose.toml`, applied after its drips resolve
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Override {
+    /// leave the pill out
+    #[serde(default)]
+    pub disable: bool,
+    /// where the root of site is instead
+    pub site: Option<PathBuf>,
+    /// poured along with the arrows of the pill
+    #[serde(alias = "arrow", default)]
+    pub arrows: Vec<Arrow>,
+}
+
+impl Machine {
+    /// the machine evaluated with `env` instead of its own tags
+    pub fn synthetic(&self, name: String, env: HashSet<String>) -> Self {
+        Machine {
+            name,
+            env,
+            facts: Facts::default(),
+            local: self.local.clone(),
+            synthetic: true,
+            overrides: IndexMap::new(),
+            assume: Some(true),
+        }
+    }
 }
 
 mod parse {
@@ -17,6 +58,8 @@ mod parse {
     pub struct Machine {
         pub env: HashSet<String>,
         pub repo: Repo,
+        #[serde(rename = "override", default)]
+        pub overrides: IndexMap<String, Override>,
         pub tutorial: Option<()>,
     }
 
@@ -44,17 +87,22 @@ impl TryFrom<parse::Machine> for Machine {
         parse::Machine {
             env,
             repo: parse::Repo { name, local },
+            overrides,
             tutorial,
         }: parse::Machine,
     ) -> Result<Self, Self::Error> {
-        if let Some(_) = tutorial {
+        if tutorial.is_some() {
             Err(anyhow::anyhow!("tutorial has not been completed yet"))?;
         }
 
         Ok(Self {
             name,
             env,
+            facts: Facts::detect(),
             local: path::expand_home(&local),
+            synthetic: false,
+            overrides,
+            assume: None,
         })
     }
 }
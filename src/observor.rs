@@ -1 +1,160 @@
+use git2::{Branch, Repository, Status, StatusOptions};
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
 
+/// the observed state of a git clone on site
+#[derive(Debug, Default)]
+pub struct GitState {
+    /// the tracking branch of the current head, if any
+    pub upstream: Option<String>,
+    /// commits on head not yet on the tracking branch, or on any remote
+    /// branch if there is none
+    pub ahead: usize,
+    /// commits on the tracking branch not yet on head
+    pub behind: usize,
+    /// tracked files with uncommitted changes, staged or not
+    pub modified: Vec<PathBuf>,
+    /// files not known to git
+    pub untracked: Vec<PathBuf>,
+}
+
+impl GitState {
+    /// observe the clone at `site`; `None` if there is no git repo there
+    pub fn observe(site: &Path) -> anyhow::Result<Option<Self>> {
+        let site = crate::utils::path::expand_home(site);
+        if !site.exists() {
+            return Ok(None);
+        }
+        let Ok(repo) = Repository::open(&site) else {
+            return Ok(None);
+        };
+        let mut state = GitState::default();
+
+        let mut opts = StatusOptions::new();
+        opts.include_untracked(true).recurse_untracked_dirs(true);
+        let statuses = repo
+            .statuses(Some(&mut opts))
+            .map_err(|e| anyhow::anyhow!("failed to read status of {}: {}", site.display(), e))?;
+        for entry in statuses.iter() {
+            let status = entry.status();
+            let Some(path) = entry.path() else {
+                continue;
+            };
+            if status.contains(Status::IGNORED) || status.is_empty() {
+                continue;
+            } else if status.contains(Status::WT_NEW) {
+                state.untracked.push(PathBuf::from(path));
+            } else {
+                state.modified.push(PathBuf::from(path));
+            }
+        }
+
+        // an unborn head has nothing to compare against
+        let Ok(head) = repo.head() else {
+            return Ok(Some(state));
+        };
+        let upstream = if head.is_branch() {
+            Branch::wrap(head).upstream().ok()
+        } else {
+            None
+        };
+        let Some(upstream) = upstream else {
+            // a detached head or a branch without upstream is pushed only if
+            // some remote branch has its commits
+            if let Some(head) = repo.head()?.target() {
+                state.ahead = unpushed(&repo, head)?;
+            }
+            return Ok(Some(state));
+        };
+        state.upstream = upstream.name()?.map(str::to_owned);
+        let head = repo.head()?.target();
+        let upstream = upstream.get().target();
+        if let (Some(local), Some(upstream)) = (head, upstream) {
+            let (ahead, behind) = repo.graph_ahead_behind(local, upstream)?;
+            state.ahead = ahead;
+            state.behind = behind;
+        }
+        Ok(Some(state))
+    }
+
+    /// whether dumping the clone would lose nothing
+    pub fn is_clean(&self) -> bool {
+        self.ahead == 0 && self.modified.is_empty() && self.untracked.is_empty()
+    }
+}
+
+/// commits reachable from `head` but from no remote branch
+fn unpushed(repo: &Repository, head: git2::Oid) -> anyhow::Result<usize> {
+    let mut walk = repo.revwalk()?;
+    walk.push(head)?;
+    for reference in repo.references_glob("refs/remotes/*")? {
+        if let Some(target) = reference?.target() {
+            walk.hide(target)?;
+        }
+    }
+    Ok(walk.count())
+}
+
+impl Display for GitState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        match &self.upstream {
+            | Some(upstream) => {
+                if self.ahead > 0 || self.behind > 0 {
+                    parts.push(format!(
+                        "{} ahead, {} behind {}",
+                        self.ahead, self.behind, upstream
+                    ))
+                }
+            }
+            | None if self.ahead > 0 => parts.push(format!(
+                "no tracking branch, {} not on any remote",
+                self.ahead
+            )),
+            | None => parts.push("no tracking branch".to_owned()),
+        }
+        if !self.modified.is_empty() {
+            parts.push(format!("{} modified", self.modified.len()));
+        }
+        if !self.untracked.is_empty() {
+            parts.push(format!("{} untracked", self.untracked.len()));
+        }
+        if parts.is_empty() {
+            write!(f, "clean")
+        } else {
+            write!(f, "{}", parts.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unpushed_commits() {
+        let site = std::env::temp_dir().join(format!("underdose-git-{}", std::process::id()));
+        let repo = Repository::init(&site).unwrap();
+        let sig = git2::Signature::now("ud", "ud@localhost").unwrap();
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        let commit = repo
+            .commit(Some("HEAD"), &sig, &sig, "init", &tree, &[])
+            .unwrap();
+        let local = GitState::observe(&site).unwrap().unwrap();
+        repo.set_head_detached(commit).unwrap();
+        let detached = GitState::observe(&site).unwrap().unwrap();
+        repo.reference("refs/remotes/origin/main", commit, false, "")
+            .unwrap();
+        let pushed = GitState::observe(&site).unwrap().unwrap();
+        std::fs::remove_dir_all(&site).unwrap();
+
+        assert_eq!(local.ahead, 1);
+        assert!(!local.is_clean());
+        assert!(!detached.is_clean());
+        assert!(pushed.is_clean());
+    }
+}
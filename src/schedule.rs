@@ -0,0 +1,122 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::Display,
+};
+
+/// a step in a sync
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Node {
+    Cmd(String),
+    Pill(String),
+}
+
+impl Display for Node {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            | Node::Cmd(name) => write!(f, "command <{}>", name),
+            | Node::Pill(name) => write!(f, "pill <{}>", name),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Schedule {
+    /// all steps in the order of execution
+    pub order: Vec<Node>,
+    /// steps pulled in along with a step
+    pub requires: HashMap<Node, Vec<Node>>,
+}
+
+impl Schedule {
+    /// sort `nodes` so that every node comes after what it's `after`, keeping
+    /// the given order wherever there's a choice; `requires` implies `after`
+    pub fn new(
+        nodes: Vec<Node>, mut after: HashMap<Node, Vec<Node>>, requires: HashMap<Node, Vec<Node>>,
+    ) -> anyhow::Result<Self> {
+        for (node, deps) in requires.iter() {
+            after
+                .entry(node.clone())
+                .or_default()
+                .extend(deps.iter().cloned());
+        }
+        let index: HashMap<&Node, usize> = nodes.iter().enumerate().map(|(i, n)| (n, i)).collect();
+        let mut pending = vec![0; nodes.len()];
+        let mut successors = vec![Vec::new(); nodes.len()];
+        for (node, deps) in after.iter() {
+            let i = index[node];
+            for dep in deps.iter().collect::<HashSet<_>>() {
+                let d = index[dep];
+                pending[i] += 1;
+                successors[d].push(i);
+            }
+        }
+
+        let mut ready: BTreeSet<usize> = (0..nodes.len()).filter(|&i| pending[i] == 0).collect();
+        let mut order = Vec::new();
+        while let Some(i) = ready.pop_first() {
+            order.push(nodes[i].clone());
+            for &s in successors[i].iter() {
+                pending[s] -= 1;
+                if pending[s] == 0 {
+                    ready.insert(s);
+                }
+            }
+        }
+        if order.len() < nodes.len() {
+            let cycle: Vec<_> = (0..nodes.len())
+                .filter(|&i| pending[i] > 0)
+                .map(|i| nodes[i].to_string())
+                .collect();
+            anyhow::bail!("dependency cycle among {}", cycle.join(", "));
+        }
+        Ok(Schedule { order, requires })
+    }
+
+    /// the picked steps and everything they require, in the order of execution
+    pub fn select(&self, picked: impl IntoIterator<Item = Node>) -> Vec<Node> {
+        let mut selected = HashSet::new();
+        let mut worklist: Vec<_> = picked.into_iter().collect();
+        while let Some(node) = worklist.pop() {
+            if let Some(deps) = self.requires.get(&node) {
+                worklist.extend(deps.iter().filter(|dep| !selected.contains(*dep)).cloned());
+            }
+            selected.insert(node);
+        }
+        self.order
+            .iter()
+            .filter(|node| selected.contains(*node))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schedule_order() {
+        let [a, b, c, d] = ["a", "b", "c", "d"].map(|s| Node::Pill(s.to_owned()));
+        let boot = Node::Cmd("boot".to_owned());
+        let nodes = vec![boot.clone(), a.clone(), b.clone(), c.clone(), d.clone()];
+        // boot runs after a; a requires c; d comes after b if both are there
+        let after = HashMap::from([
+            (boot.clone(), vec![a.clone()]),
+            (d.clone(), vec![b.clone()]),
+        ]);
+        let requires = HashMap::from([(a.clone(), vec![c.clone()])]);
+        let schedule = Schedule::new(nodes.clone(), after, requires).unwrap();
+        assert_eq!(
+            schedule.order,
+            [b.clone(), c.clone(), a.clone(), boot.clone(), d.clone()]
+        );
+        assert_eq!(schedule.select(vec![boot.clone()]), vec![boot.clone()]);
+        assert_eq!(
+            schedule.select([a.clone(), d.clone()]),
+            [c.clone(), a.clone(), d.clone()]
+        );
+
+        let after = HashMap::from([(a.clone(), vec![b.clone()]), (b.clone(), vec![a.clone()])]);
+        assert!(Schedule::new(nodes, after, HashMap::new()).is_err());
+    }
+}
@@ -0,0 +1,347 @@
+use crate::{
+    Arrow, ArrowSrc, Drip, Generator,
+    utils::{global::UNDERDOSE_PATH, merge},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+/// hashes of what has been poured onto each site, so that generated sites are
+/// only poured again when their sources change; written back only by
+/// `write_index`
+#[derive(Default, Serialize, Deserialize, Debug)]
+pub struct Stamps {
+    pub map: HashMap<PathBuf, String>,
+    /// stamps worked out in this run, by site, so that pouring reuses them
+    #[serde(skip)]
+    expected: RefCell<HashMap<PathBuf, String>>,
+}
+
+impl Stamps {
+    fn index_path() -> &'static Path {
+        UNDERDOSE_PATH.stamps.as_path()
+    }
+
+    pub fn new() -> Self {
+        let Ok(content) = std::fs::read_to_string(Self::index_path()) else {
+            return Self::default();
+        };
+        let Ok(res) = serde_json::from_str(&content) else {
+            return Self::default();
+        };
+        res
+    }
+
+    /// the stamp an arrow should carry once poured; `None` if the arrow is not stamped
+    pub fn expect(repo: &Path, drip: &Drip, arrow: &Arrow) -> anyhow::Result<Option<String>> {
+        match &arrow.src {
+            | ArrowSrc::Git(_)
+            | ArrowSrc::Link(_)
+            | ArrowSrc::Collector
+            | ArrowSrc::Block(_)
+            | ArrowSrc::Merge(_) => Ok(None),
+            | ArrowSrc::Archive(rel) => {
+                let archive = repo.join(&drip.rel_repo).join(rel);
+                let digest = digest_file(&archive).map_err(|e| {
+                    anyhow::anyhow!("failed to read archive {}: {}", archive.display(), e)
+                })?;
+                Ok(Some(digest))
+            }
+            | ArrowSrc::Cmd(Generator { prog, args, inputs }) => {
+                let root = repo.join(&drip.rel_repo);
+                let mut chunks = vec![prog.as_bytes().to_vec()];
+                chunks.extend(args.iter().map(|arg| arg.as_bytes().to_vec()));
+                for input in inputs {
+                    chunks.push(input.to_string_lossy().as_bytes().to_vec());
+                    read_tree(&root.join(input), &mut chunks)?;
+                }
+                Ok(Some(digest(chunks.iter().map(Vec::as_slice))))
+            }
+            | ArrowSrc::Concat(concat) => {
+                let content = concat.assemble(&repo.join(&drip.rel_repo))?;
+                Ok(Some(digest([content.as_slice()])))
+            }
+        }
+    }
+
+    /// as `expect`, but worked out once per site in a run
+    pub fn expected(
+        &self, repo: &Path, drip: &Drip, arrow: &Arrow,
+    ) -> anyhow::Result<Option<String>> {
+        let site = Self::key(drip.site.join(&arrow.rel_site));
+        if let Some(stamp) = self.expected.borrow().get(&site) {
+            return Ok(Some(stamp.clone()));
+        }
+        let stamp = Self::expect(repo, drip, arrow)?;
+        if let Some(stamp) = &stamp {
+            self.expected.borrow_mut().insert(site, stamp.clone());
+        }
+        Ok(stamp)
+    }
+
+    /// the site as stamps are kept under, with its parents resolved
+    fn key(site: impl AsRef<Path>) -> PathBuf {
+        let site = site.as_ref();
+        crate::utils::path::canonicalize(site)
+            .unwrap_or_else(|_| crate::utils::path::expand_home(site))
+    }
+
+    /// whether the arrow has to be poured again
+    pub fn is_stale(
+        &self, name: &str, repo: &Path, drip: &Drip, arrow: &Arrow,
+    ) -> anyhow::Result<bool> {
+        if let ArrowSrc::Link(rel) = &arrow.src {
+            // a link is in place if it points into repo already
+            let site = drip.site.join(&arrow.rel_site);
+            let repo = repo.join(&drip.rel_repo).join(rel);
+            let (Ok(site), Ok(repo)) = (
+                crate::utils::path::canonicalize(site),
+                crate::utils::path::canonicalize(repo),
+            ) else {
+                return Ok(true);
+            };
+            return Ok(std::fs::read_link(site).ok() != Some(repo));
+        }
+        if let ArrowSrc::Git(remote) = &arrow.src {
+            // a clone is in place if it's cloned from the remote
+            let site = crate::utils::path::expand_home(drip.site.join(&arrow.rel_site));
+            let Ok(clone) = git2::Repository::open(site) else {
+                return Ok(true);
+            };
+            let origin = clone.find_remote("origin");
+            return Ok(origin.ok().and_then(|o| o.url().map(str::to_owned)) != Some(remote.clone()));
+        }
+        if let ArrowSrc::Block(block) = &arrow.src {
+            let file = repo.join(&drip.rel_repo).join(&block.file);
+            let content = std::fs::read_to_string(&file)
+                .map_err(|e| anyhow::anyhow!("failed to read {}: {}", file.display(), e))?;
+            let site = crate::utils::path::expand_home(drip.site.join(&arrow.rel_site));
+            let site = std::fs::read_to_string(site).unwrap_or_default();
+            return Ok(block.find(name, &site) != Some(block.render(name, &content)));
+        }
+        if let ArrowSrc::Collector = &arrow.src {
+            // a collector is only ever made, and filled from site
+            let site = crate::utils::path::expand_home(drip.site.join(&arrow.rel_site));
+            return Ok(!site.exists());
+        }
+        if let ArrowSrc::Merge(rel) = &arrow.src {
+            let site = crate::utils::path::expand_home(drip.site.join(&arrow.rel_site));
+            return Ok(!merge::drift_of(&repo.join(&drip.rel_repo).join(rel), &site)?.is_empty());
+        }
+        let Some(expected) = self.expected(repo, drip, arrow)? else {
+            return Ok(true);
+        };
+        let site = Self::key(drip.site.join(&arrow.rel_site));
+        if !site.exists() {
+            return Ok(true);
+        }
+        Ok(self.map.get(&site) != Some(&expected))
+    }
+
+    /// the part of the drip that has to be poured again
+    pub fn stale(&self, name: &str, repo: &Path, drip: &Drip) -> anyhow::Result<Drip> {
+        let mut arrows = Vec::new();
+        for arrow in drip.arrows.iter() {
+            if self.is_stale(name, repo, drip, arrow)? {
+                arrows.push(arrow.clone());
+            } else {
+                log::info!("unchanged {}", drip.site.join(&arrow.rel_site).display());
+            }
+        }
+        Ok(Drip {
+            arrows,
+            ..drip.clone()
+        })
+    }
+
+    pub fn stamp(&mut self, site: &Path, stamp: String) {
+        self.map.insert(Self::key(site), stamp);
+    }
+
+    pub fn write_index(&self) -> anyhow::Result<()> {
+        let content = serde_json::to_string(&self)
+            .map_err(|e| anyhow::anyhow!("failed to serialize stamps: {}", e))?;
+        crate::utils::path::create_dir_parent(Self::index_path())?;
+        std::fs::write(Self::index_path(), content)
+            .map_err(|e| anyhow::anyhow!("failed to write stamps: {}", e))?;
+        log::trace!("dumped stamps at {}", Self::index_path().display());
+        Ok(())
+    }
+}
+
+/// contents of a file, or of every file under a directory in a stable order
+fn read_tree(path: &Path, chunks: &mut Vec<Vec<u8>>) -> anyhow::Result<()> {
+    if path.is_dir() {
+        let mut entries = std::fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        entries.sort();
+        for entry in entries {
+            chunks.push(entry.to_string_lossy().as_bytes().to_vec());
+            read_tree(&entry, chunks)?;
+        }
+    } else {
+        let content = std::fs::read(path)
+            .map_err(|e| anyhow::anyhow!("failed to read input {}: {}", path.display(), e))?;
+        chunks.push(content);
+    }
+    Ok(())
+}
+
+/// `digest` of the content of a file as a single chunk, read as a stream
+fn digest_file(path: &Path) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    hasher.update(file.metadata()?.len().to_le_bytes());
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(hex(hasher))
+}
+
+/// sha256 over a sequence of byte chunks, in hex
+pub fn digest<'a>(chunks: impl IntoIterator<Item = &'a [u8]>) -> String {
+    let mut hasher = Sha256::new();
+    for chunk in chunks {
+        // length-prefixed so that chunk boundaries matter
+        hasher.update((chunk.len() as u64).to_le_bytes());
+        hasher.update(chunk);
+    }
+    hex(hasher)
+}
+
+fn hex(hasher: Sha256) -> String {
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn stamp_through_symlink() {
+        let root = std::env::temp_dir().join(format!("underdose-key-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("real")).unwrap();
+        std::os::unix::fs::symlink(root.join("real"), root.join("alias")).unwrap();
+        std::fs::write(root.join("real/site"), "archived").unwrap();
+        let streamed = digest_file(&root.join("real/site")).unwrap();
+        let mut stamps = Stamps::default();
+        stamps.stamp(&root.join("real/site"), streamed.clone());
+        let found = stamps
+            .map
+            .get(&Stamps::key(root.join("alias/site")))
+            .cloned();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(streamed, digest([b"archived".as_slice()]));
+        assert_eq!(found, Some(streamed));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn stale_in_place() {
+        let root = std::env::temp_dir().join(format!("underdose-stamps-{}", std::process::id()));
+        let (repo, site) = (root.join("repo"), root.join("site"));
+        std::fs::create_dir_all(repo.join("vim")).unwrap();
+        std::fs::create_dir_all(&site).unwrap();
+        std::fs::write(repo.join("vim/vimrc"), "").unwrap();
+        let arrow = |rel_site: &str, src: ArrowSrc| Arrow {
+            rel_site: PathBuf::from(rel_site),
+            src,
+            cond: None,
+        };
+        let link = arrow(".vimrc", ArrowSrc::Link(PathBuf::from("vimrc")));
+        let git = arrow(
+            "plug",
+            ArrowSrc::Git("https://example.com/plug.git".to_owned()),
+        );
+        let collector = arrow("undo", ArrowSrc::Collector);
+        let drip = Drip {
+            site: site.clone(),
+            rel_repo: PathBuf::from("vim"),
+            arrows: vec![link.clone(), git.clone(), collector.clone()],
+        };
+        let stamps = Stamps::default();
+        let stale = |arrow: &Arrow| stamps.is_stale("vim", &repo, &drip, arrow).unwrap();
+        let missing = [&link, &git, &collector].map(stale);
+
+        std::os::unix::fs::symlink(repo.join("vim/vimrc"), site.join(".vimrc")).unwrap();
+        let clone = git2::Repository::init(site.join("plug")).unwrap();
+        clone
+            .remote("origin", "https://example.com/other.git")
+            .unwrap();
+        let other_remote = stale(&git);
+        clone
+            .remote_set_url("origin", "https://example.com/plug.git")
+            .unwrap();
+        std::fs::create_dir(site.join("undo")).unwrap();
+        let poured = [&link, &git, &collector].map(stale);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(missing, [true, true, true]);
+        assert!(other_remote);
+        assert_eq!(poured, [false, false, false]);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn stale_generated() {
+        let root = std::env::temp_dir().join(format!("underdose-gen-{}", std::process::id()));
+        let (repo, site) = (root.join("repo"), root.join("site"));
+        std::fs::create_dir_all(repo.join("dict")).unwrap();
+        std::fs::create_dir_all(&site).unwrap();
+        std::fs::write(repo.join("dict/words"), "pill\n").unwrap();
+        std::fs::write(repo.join("dict/notes"), "").unwrap();
+        let arrow = Arrow {
+            rel_site: PathBuf::from("words.txt"),
+            src: ArrowSrc::Cmd(Generator {
+                prog: "sh".to_owned(),
+                args: vec!["-c".to_owned(), "cat words".to_owned()],
+                inputs: vec![PathBuf::from("words")],
+            }),
+            cond: None,
+        };
+        let drip = Drip {
+            site: site.clone(),
+            rel_repo: PathBuf::from("dict"),
+            arrows: vec![arrow.clone()],
+        };
+        let mut stamps = Stamps::default();
+        let missing = stamps.is_stale("dict", &repo, &drip, &arrow).unwrap();
+        crate::Executor {
+            name: "dict",
+            repo: &repo,
+            drip: &drip,
+            stamps: &mut stamps,
+        }
+        .run()
+        .unwrap();
+        let output = std::fs::read_to_string(site.join("words.txt")).unwrap();
+        // each run works its stamps out afresh
+        let stale = || {
+            let stamps = Stamps {
+                map: stamps.map.clone(),
+                ..Default::default()
+            };
+            stamps.is_stale("dict", &repo, &drip, &arrow).unwrap()
+        };
+        let poured = stale();
+        std::fs::write(repo.join("dict/notes"), "not an input").unwrap();
+        let other_changed = stale();
+        std::fs::write(repo.join("dict/words"), "pill\ndrip\n").unwrap();
+        let input_changed = stale();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert!(missing);
+        assert_eq!(output, "pill\n");
+        assert!(!poured && !other_changed);
+        assert!(input_changed);
+    }
+}
@@ -5,6 +5,8 @@ use std::path::PathBuf;
 pub struct UnderdoseStatics {
     pub conf: PathBuf,
     pub dreams: PathBuf,
+    pub stamps: PathBuf,
+    pub ledger: PathBuf,
 }
 
 pub struct ProjectDirs;
@@ -21,4 +23,6 @@ impl AppAuthor for ProjectDirs {
 pub static UNDERDOSE_PATH: Lazy<UnderdoseStatics> = Lazy::new(|| UnderdoseStatics {
     conf: ProjectDirs::config_dir().join("Underdose.toml"),
     dreams: ProjectDirs::data_dir().join("dreams"),
+    stamps: ProjectDirs::data_dir().join("stamps.json"),
+    ledger: ProjectDirs::data_dir().join("ledger.json"),
 });
@@ -0,0 +1,492 @@
+use serde_json::Value;
+use std::path::Path;
+use toml_edit::{DocumentMut, TableLike};
+
+/// formats of settings files that can be merged into
+#[derive(Debug, Clone, Copy)]
+pub enum Format {
+    Json,
+    Toml,
+}
+
+impl Format {
+    pub fn of(path: &Path) -> anyhow::Result<Self> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            | Some("json" | "jsonc") => Ok(Format::Json),
+            | Some("toml") => Ok(Format::Toml),
+            | _ => anyhow::bail!("unknown settings format of <{}>", path.display()),
+        }
+    }
+
+    /// plain values of the file, for comparison
+    fn values(self, buf: &str) -> anyhow::Result<Value> {
+        if buf.trim().is_empty() {
+            return Ok(Value::Object(Default::default()));
+        }
+        match self {
+            | Format::Json => Ok(serde_json::from_str(&strip_jsonc(buf))?),
+            | Format::Toml => Ok(serde_json::to_value(toml::from_str::<toml::Table>(buf)?)?),
+        }
+    }
+}
+
+fn read(path: &Path) -> anyhow::Result<String> {
+    std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("failed to read {}: {}", path.display(), e))
+}
+
+/// read a site file, which may not exist yet
+fn read_site(path: &Path) -> anyhow::Result<String> {
+    match std::fs::read_to_string(path) {
+        | Ok(buf) => Ok(buf),
+        | Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+        | Err(e) => anyhow::bail!("failed to read {}: {}", path.display(), e),
+    }
+}
+
+/// merge the fragment file into the site file
+pub fn pour(fragment: &Path, site: &Path) -> anyhow::Result<()> {
+    let format = Format::of(fragment)?;
+    let merged = merge(format, &read_site(site)?, &read(fragment)?)
+        .map_err(|e| anyhow::anyhow!("failed to merge into {}: {}", site.display(), e))?;
+    std::fs::write(site, merged)?;
+    Ok(())
+}
+
+/// keys of the fragment file that drifted in the site file
+pub fn drift_of(fragment: &Path, site: &Path) -> anyhow::Result<Vec<String>> {
+    let format = Format::of(fragment)?;
+    drift(format, &read_site(site)?, &read(fragment)?)
+        .map_err(|e| anyhow::anyhow!("failed to compare with {}: {}", site.display(), e))
+}
+
+/// deep-merge `fragment` into `site`, keeping everything in `site` that
+/// `fragment` doesn't mention
+pub fn merge(format: Format, site: &str, fragment: &str) -> anyhow::Result<String> {
+    match format {
+        | Format::Json if site.trim().is_empty() => {
+            let mut res = serde_json::to_string_pretty(&format.values(fragment)?)?;
+            res.push('\n');
+            Ok(res)
+        }
+        | Format::Json => {
+            // edited in place, keeping comments and layout of the site file
+            let Jsonc::Object(root) = Scanner::new(site).document()? else {
+                anyhow::bail!("the top level is not an object");
+            };
+            let Value::Object(fragment) = format.values(fragment)? else {
+                anyhow::bail!("the top level of the fragment is not an object");
+            };
+            let mut edits = Vec::new();
+            edit_json(site, &root, fragment, &mut edits);
+            edits.sort_by_key(|(start, _, _)| std::cmp::Reverse(*start));
+            let mut res = site.to_owned();
+            for (start, end, text) in edits {
+                res.replace_range(start..end, &text);
+            }
+            Ok(res)
+        }
+        | Format::Toml => {
+            let mut site = site.parse::<DocumentMut>()?;
+            let fragment = fragment.parse::<DocumentMut>()?;
+            merge_toml(site.as_table_mut(), fragment.as_table());
+            Ok(site.to_string())
+        }
+    }
+}
+
+/// a value in a json file that may have comments and trailing commas; objects
+/// keep where their members are, so that they can be edited in place
+enum Jsonc {
+    Object(JsoncObject),
+    Other,
+}
+
+struct JsoncObject {
+    /// offset of `{`
+    open: usize,
+    members: Vec<JsoncMember>,
+}
+
+struct JsoncMember {
+    key: String,
+    key_start: usize,
+    value: Jsonc,
+    /// offsets of the value, end exclusive
+    start: usize,
+    end: usize,
+}
+
+struct Scanner<'a> {
+    buf: &'a str,
+    pos: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(buf: &'a str) -> Self {
+        Scanner { buf, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.buf.as_bytes().get(self.pos).copied()
+    }
+
+    fn fail<T>(&self, what: &str) -> anyhow::Result<T> {
+        let line = self.buf[..self.pos.min(self.buf.len())]
+            .matches('\n')
+            .count()
+            + 1;
+        anyhow::bail!("{} at line {}", what, line)
+    }
+
+    /// skip whitespace and comments
+    fn skip(&mut self) -> anyhow::Result<()> {
+        loop {
+            let rest = &self.buf[self.pos..];
+            if rest.starts_with("//") {
+                self.pos += rest.find('\n').unwrap_or(rest.len());
+            } else if rest.starts_with("/*") {
+                let Some(end) = rest.find("*/") else {
+                    return self.fail("unterminated comment");
+                };
+                self.pos += end + 2;
+            } else if self.peek().is_some_and(|c| c.is_ascii_whitespace()) {
+                self.pos += 1;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> anyhow::Result<()> {
+        self.skip()?;
+        if self.peek() != Some(c) {
+            return self.fail(&format!("expected `{}`", c as char));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    /// the whole file as a single value
+    fn document(mut self) -> anyhow::Result<Jsonc> {
+        let value = self.value()?;
+        self.skip()?;
+        if self.pos < self.buf.len() {
+            return self.fail("unexpected content");
+        }
+        Ok(value)
+    }
+
+    fn value(&mut self) -> anyhow::Result<Jsonc> {
+        self.skip()?;
+        match self.peek() {
+            | Some(b'{') => self.object().map(Jsonc::Object),
+            | Some(b'[') => {
+                self.pos += 1;
+                self.items(b']', |scanner| scanner.value().map(|_| ()))?;
+                Ok(Jsonc::Other)
+            }
+            | Some(b'"') => self.string().map(|_| Jsonc::Other),
+            | Some(_) => {
+                let rest = &self.buf[self.pos..];
+                let len = rest
+                    .find(|c: char| c.is_ascii_whitespace() || ",:]}/".contains(c))
+                    .unwrap_or(rest.len());
+                if len == 0 {
+                    return self.fail("expected a value");
+                }
+                self.pos += len;
+                Ok(Jsonc::Other)
+            }
+            | None => self.fail("expected a value"),
+        }
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        self.skip()?;
+        let start = self.pos;
+        if self.peek() != Some(b'"') {
+            return self.fail("expected a string");
+        }
+        self.pos += 1;
+        loop {
+            match self.peek() {
+                | Some(b'\\') => self.pos += 2,
+                | Some(b'"') => break,
+                | Some(_) => self.pos += 1,
+                | None => return self.fail("unterminated string"),
+            }
+        }
+        self.pos += 1;
+        Ok(serde_json::from_str(&self.buf[start..self.pos])?)
+    }
+
+    /// comma separated items up to `close`, which may follow a last comma
+    fn items(
+        &mut self, close: u8, mut item: impl FnMut(&mut Self) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        loop {
+            self.skip()?;
+            if self.peek() == Some(close) {
+                self.pos += 1;
+                return Ok(());
+            }
+            item(self)?;
+            self.skip()?;
+            match self.peek() {
+                | Some(b',') => self.pos += 1,
+                | Some(c) if c == close => {}
+                | _ => return self.fail(&format!("expected `,` or `{}`", close as char)),
+            }
+        }
+    }
+
+    fn object(&mut self) -> anyhow::Result<JsoncObject> {
+        let open = self.pos;
+        self.pos += 1;
+        let mut members = Vec::new();
+        self.items(b'}', |scanner| {
+            scanner.skip()?;
+            let key_start = scanner.pos;
+            let key = scanner.string()?;
+            scanner.expect(b':')?;
+            scanner.skip()?;
+            let start = scanner.pos;
+            let value = scanner.value()?;
+            members.push(JsoncMember {
+                key,
+                key_start,
+                value,
+                start,
+                end: scanner.pos,
+            });
+            Ok(())
+        })?;
+        Ok(JsoncObject { open, members })
+    }
+}
+
+/// `buf` as plain json, with comments and trailing commas taken out
+fn strip_jsonc(buf: &str) -> String {
+    let mut res = String::with_capacity(buf.len());
+    let mut chars = buf.chars().peekable();
+    let mut in_string = false;
+    // a comma is held back until it's known not to be trailing
+    let mut comma = false;
+    while let Some(c) = chars.next() {
+        if in_string {
+            res.push(c);
+            match c {
+                | '\\' => res.extend(chars.next()),
+                | '"' => in_string = false,
+                | _ => {}
+            }
+            continue;
+        }
+        match (c, chars.peek()) {
+            | ('/', Some('/')) => while chars.next_if(|c| *c != '\n').is_some() {},
+            | ('/', Some('*')) => {
+                chars.next();
+                let mut last = ' ';
+                for c in chars.by_ref() {
+                    if last == '*' && c == '/' {
+                        break;
+                    }
+                    last = c;
+                }
+            }
+            | (c, _) if c.is_whitespace() => res.push(c),
+            | (',', _) => comma = true,
+            | (c, _) => {
+                if comma && c != '}' && c != ']' {
+                    res.push(',');
+                }
+                comma = false;
+                in_string = c == '"';
+                res.push(c);
+            }
+        }
+    }
+    res
+}
+
+/// leading whitespace of the line `pos` is on
+fn indent_of(buf: &str, pos: usize) -> &str {
+    let start = buf[..pos].rfind('\n').map_or(0, |i| i + 1);
+    let line = &buf[start..];
+    &line[..line.len() - line.trim_start().len()]
+}
+
+/// `value` as pretty json, continued lines indented by `indent`
+fn render(value: &Value, indent: &str) -> String {
+    let pretty = serde_json::to_string_pretty(value).expect("json values serialize");
+    pretty.replace('\n', &format!("\n{}", indent))
+}
+
+/// edits to `site` that deep-merge `fragment` into `object`, as ranges of
+/// `site` and their replacements
+fn edit_json(
+    site: &str, object: &JsoncObject, fragment: serde_json::Map<String, Value>,
+    edits: &mut Vec<(usize, usize, String)>,
+) {
+    let mut added = Vec::new();
+    for (k, v) in fragment {
+        let Some(member) = object.members.iter().find(|m| m.key == k) else {
+            added.push((k, v));
+            continue;
+        };
+        match (&member.value, v) {
+            | (Jsonc::Object(inner), Value::Object(v)) => edit_json(site, inner, v, edits),
+            | (_, v) => {
+                let text = render(&v, indent_of(site, member.key_start));
+                edits.push((member.start, member.end, text));
+            }
+        }
+    }
+    if added.is_empty() {
+        return;
+    }
+    let key = |k: &str| serde_json::to_string(k).expect("strings serialize");
+    let text = match object.members.last() {
+        | None => {
+            let outer = indent_of(site, object.open);
+            let inner = format!("{}{}", outer, indent_unit(site));
+            let members: Vec<_> = (added.iter())
+                .map(|(k, v)| format!("\n{}{}: {}", inner, key(k), render(v, &inner)))
+                .collect();
+            edits.push((
+                object.open + 1,
+                object.open + 1,
+                members.join(",") + "\n" + outer,
+            ));
+            return;
+        }
+        // an object on a single line stays on it
+        | Some(last) if !site[object.open..last.end].contains('\n') => (added.iter())
+            .map(|(k, v)| format!(", {}: {}", key(k), v))
+            .collect::<String>(),
+        | Some(last) => {
+            let indent = indent_of(site, last.key_start);
+            (added.iter())
+                .map(|(k, v)| format!(",\n{}{}: {}", indent, key(k), render(v, indent)))
+                .collect::<String>()
+        }
+    };
+    let end = object
+        .members
+        .last()
+        .map_or(object.open + 1, |last| last.end);
+    edits.push((end, end, text));
+}
+
+/// the indentation a file uses for one level
+fn indent_unit(buf: &str) -> &str {
+    (buf.lines())
+        .map(|line| &line[..line.len() - line.trim_start().len()])
+        .find(|indent| !indent.is_empty())
+        .unwrap_or("  ")
+}
+
+fn merge_toml(site: &mut dyn TableLike, fragment: &dyn TableLike) {
+    for (k, v) in fragment.iter() {
+        let both = site.get_mut(k).and_then(|old| old.as_table_like_mut());
+        match (both, v.as_table_like()) {
+            | (Some(old), Some(new)) => merge_toml(old, new),
+            | _ => {
+                site.insert(k, v.clone());
+            }
+        }
+    }
+}
+
+/// dotted keys managed by `fragment` whose values differ in `site`
+pub fn drift(format: Format, site: &str, fragment: &str) -> anyhow::Result<Vec<String>> {
+    let mut res = Vec::new();
+    drift_value(
+        Some(&format.values(site)?),
+        &format.values(fragment)?,
+        "",
+        &mut res,
+    );
+    Ok(res)
+}
+
+fn drift_value(site: Option<&Value>, fragment: &Value, key: &str, res: &mut Vec<String>) {
+    match (site, fragment) {
+        | (Some(Value::Object(site)), Value::Object(fragment)) => {
+            for (k, v) in fragment {
+                let key = if key.is_empty() {
+                    k.to_owned()
+                } else {
+                    format!("{}.{}", key, k)
+                };
+                drift_value(site.get(k), v, &key, res);
+            }
+        }
+        | (site, fragment) => {
+            if site != Some(fragment) {
+                res.push(key.to_owned());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_keeps_foreign_keys() {
+        let site = r#"{ "editor": { "fontSize": 12, "tabSize": 2 }, "window.zoom": 1 }"#;
+        let fragment = r#"{ "editor": { "fontSize": 14 }, "telemetry": false }"#;
+        assert_eq!(
+            drift(Format::Json, site, fragment).unwrap(),
+            ["editor.fontSize", "telemetry"]
+        );
+        let merged = merge(Format::Json, site, fragment).unwrap();
+        assert!(drift(Format::Json, &merged, fragment).unwrap().is_empty());
+        let merged: Value = serde_json::from_str(&merged).unwrap();
+        assert_eq!(merged["editor"]["tabSize"], 2);
+        assert_eq!(merged["window.zoom"], 1);
+
+        let site = r#"{
+    // written by the app
+    "editor": {
+        "fontSize": 12, /* default */
+        "tabSize": 2,
+    },
+    "extensions": {},
+}
+"#;
+        let fragment = r#"{ "editor": { "fontSize": 14, "wordWrap": "on" }, "extensions": { "auto": false } }"#;
+        assert_eq!(
+            drift(Format::Json, site, fragment).unwrap(),
+            ["editor.fontSize", "editor.wordWrap", "extensions.auto"]
+        );
+        let merged = merge(Format::Json, site, fragment).unwrap();
+        assert_eq!(
+            merged,
+            r#"{
+    // written by the app
+    "editor": {
+        "fontSize": 14, /* default */
+        "tabSize": 2,
+        "wordWrap": "on",
+    },
+    "extensions": {
+        "auto": false
+    },
+}
+"#
+        );
+        assert!(drift(Format::Json, &merged, fragment).unwrap().is_empty());
+
+        let site = "# written by the app\n[font]\nsize = 12\nfamily = \"mono\"\n";
+        let fragment = "[font]\nsize = 14\n";
+        let merged = merge(Format::Toml, site, fragment).unwrap();
+        assert_eq!(
+            merged,
+            "# written by the app\n[font]\nsize = 14\nfamily = \"mono\"\n"
+        );
+    }
+}
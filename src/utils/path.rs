@@ -12,6 +12,45 @@ pub fn expand_home<P: AsRef<Path>>(path: P) -> PathBuf {
     PathBuf::from(shellexpand::path::tilde(path.as_ref()))
 }
 
+/// look up a program on `PATH`, or check it directly if it's a path
+pub fn which(prog: &str) -> Option<PathBuf> {
+    let prog = expand_home(prog);
+    if prog.components().count() > 1 {
+        return executable(&prog);
+    }
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths).find_map(|dir| executable(&dir.join(&prog)))
+}
+
+/// the program at `path`, if it's a file that can be run
+#[cfg(unix)]
+fn executable(path: &Path) -> Option<PathBuf> {
+    use std::os::unix::fs::PermissionsExt;
+    let meta = std::fs::metadata(path).ok()?;
+    (meta.is_file() && meta.permissions().mode() & 0o111 != 0).then(|| path.to_owned())
+}
+
+/// the program at `path`, trying each suffix in `PATHEXT` unless it has one
+#[cfg(windows)]
+fn executable(path: &Path) -> Option<PathBuf> {
+    if path.extension().is_some() && path.is_file() {
+        return Some(path.to_owned());
+    }
+    let exts = std::env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_owned());
+    (exts.split(';').filter(|ext| !ext.is_empty()))
+        .map(|ext| {
+            let mut path = path.as_os_str().to_owned();
+            path.push(ext);
+            PathBuf::from(path)
+        })
+        .find(|path| path.is_file())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn executable(path: &Path) -> Option<PathBuf> {
+    path.is_file().then(|| path.to_owned())
+}
+
 pub fn canonicalize<P: AsRef<Path>>(path: P) -> anyhow::Result<PathBuf> {
     let path = expand_home(path);
     let parent = path
@@ -51,3 +90,24 @@ pub fn create_dir_parent<P: AsRef<Path>>(path: P) -> anyhow::Result<()> {
 //     let res = parent.join(file_name);
 //     Ok(res)
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn which_executable() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("underdose-which-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (script, data) = (dir.join("script"), dir.join("data"));
+        std::fs::write(&script, "#!/bin/sh\n").unwrap();
+        std::fs::write(&data, "").unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let found = [&script, &data].map(|path| which(&path.to_string_lossy()));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(found, [Some(script), None]);
+    }
+}